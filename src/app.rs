@@ -0,0 +1,161 @@
+//! Authenticating as a GitHub App installation, with short-lived tokens minted from the app's
+//! private key.
+
+use crate::{error_for_status, Timestamp};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use reqwest::header::{HeaderValue, InvalidHeaderValue, AUTHORIZATION};
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+use std::path::Path;
+use std::time::Duration;
+
+/// How long before it expires an installation token is replaced, so that requests in flight
+/// don't carry a token that runs out on the way.
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// What identifies an installation of a GitHub App.
+pub struct AppCredentials {
+    app_id: u64,
+    installation_id: u64,
+    key: RsaKeyPair,
+}
+
+impl AppCredentials {
+    /// Reads the app's private key, a PEM file as downloaded from the app's settings.
+    pub fn load(
+        app_id: u64,
+        installation_id: u64,
+        private_key_file: &Path,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let read_error = |e: std::io::Error| {
+            format!(
+                "Failed to read private key file {}: {}",
+                private_key_file.display(),
+                e
+            )
+        };
+        let pem = std::fs::read(private_key_file).map_err(read_error)?;
+        let key = match rustls_pemfile::read_one(&mut pem.as_slice()).map_err(read_error)? {
+            Some(rustls_pemfile::Item::RSAKey(der)) => RsaKeyPair::from_der(&der),
+            Some(rustls_pemfile::Item::PKCS8Key(der)) => RsaKeyPair::from_pkcs8(&der),
+            _ => {
+                return Err(format!(
+                    "Private key file {} holds no RSA private key",
+                    private_key_file.display()
+                )
+                .into())
+            }
+        }
+        .map_err(|e| {
+            format!(
+                "Invalid private key in {}: {}",
+                private_key_file.display(),
+                e
+            )
+        })?;
+        Ok(AppCredentials {
+            app_id,
+            installation_id,
+            key,
+        })
+    }
+
+    /// A JSON Web Token authenticating as the app itself, valid for ten minutes.
+    fn jwt(&self, now: Timestamp) -> Result<String, Box<dyn std::error::Error>> {
+        // Backdated a minute to allow for clocks running ahead of GitHub's.
+        let claims = serde_json::json!({
+            "iat": now.unix() - 60,
+            "exp": now.unix() + 9 * 60,
+            "iss": self.app_id.to_string(),
+        });
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let message = format!("{}.{}", header, URL_SAFE_NO_PAD.encode(claims.to_string()));
+
+        let mut signature = vec![0; self.key.public().modulus_len()];
+        self.key
+            .sign(
+                &RSA_PKCS1_SHA256,
+                &SystemRandom::new(),
+                message.as_bytes(),
+                &mut signature,
+            )
+            .map_err(|_| "Failed to sign the GitHub App token")?;
+        Ok(format!("{}.{}", message, URL_SAFE_NO_PAD.encode(signature)))
+    }
+}
+
+/// A token acting as an installation of a GitHub App, valid for an hour.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct InstallationToken {
+    pub token: String,
+    pub expires_at: Timestamp,
+}
+
+/// Mints a token for the installation of `credentials` through the apps API.
+pub async fn create_installation_token(
+    http: &reqwest::Client,
+    base_url: &str,
+    credentials: &AppCredentials,
+    timeout: Duration,
+) -> Result<InstallationToken, Box<dyn std::error::Error>> {
+    let url = format!(
+        "{}/app/installations/{}/access_tokens",
+        base_url, credentials.installation_id
+    );
+    let jwt = credentials.jwt(Timestamp::now())?;
+    let res = http
+        .post(&url)
+        .header(AUTHORIZATION, bearer(&jwt)?)
+        .timeout(timeout)
+        .send()
+        .await?;
+    let status = res.status();
+    if !status.is_success() {
+        let e = error_for_status(status, res.text().await?);
+        return Err(format!(
+            "Failed to create a token for installation {} of app {}: {}",
+            credentials.installation_id, credentials.app_id, e
+        )
+        .into());
+    }
+    Ok(res.json().await?)
+}
+
+/// An installation token together with what it takes to replace it once it expires.
+pub(crate) struct AppAuth {
+    credentials: AppCredentials,
+    token: tokio::sync::Mutex<InstallationToken>,
+}
+
+impl AppAuth {
+    pub(crate) fn new(credentials: AppCredentials, token: InstallationToken) -> Self {
+        AppAuth {
+            credentials,
+            token: tokio::sync::Mutex::new(token),
+        }
+    }
+
+    /// The `Authorization` header of a token that is still valid, minting a new one when the
+    /// current one is about to expire.
+    pub(crate) async fn header(
+        &self,
+        http: &reqwest::Client,
+        base_url: &str,
+        timeout: Duration,
+    ) -> Result<HeaderValue, Box<dyn std::error::Error>> {
+        let mut token = self.token.lock().await;
+        if Timestamp::now() >= token.expires_at - REFRESH_MARGIN {
+            log::debug!(
+                "Installation token expires at {}, refreshing",
+                token.expires_at
+            );
+            *token = create_installation_token(http, base_url, &self.credentials, timeout).await?;
+        }
+        Ok(bearer(&token.token)?)
+    }
+}
+
+fn bearer(token: &str) -> Result<HeaderValue, InvalidHeaderValue> {
+    HeaderValue::from_str(&format!("Bearer {}", token))
+}
@@ -0,0 +1,241 @@
+//! On-disk cache of ETags and response bodies used for conditional requests.
+
+use crate::paths::write_json;
+use crate::{Repo, Timestamp};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Bumped whenever the file layout changes; files with another version are discarded.
+const CACHE_VERSION: u32 = 2;
+
+/// Pages not fetched or confirmed unchanged for this long are dropped when saving.
+const MAX_RESPONSE_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// At most this many pages are kept, the most recently used ones.
+const MAX_RESPONSES: usize = 5000;
+
+/// A cached page as last returned by GitHub.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct CachedResponse {
+    pub etag: String,
+    pub body: String,
+    /// The `rel="next"` link of the page, so pagination works on cache hits too.
+    pub next: Option<String>,
+    /// When the page was last fetched or confirmed unchanged.
+    pub used_at: Timestamp,
+}
+
+/// A cache file: entries under the URL or key they belong to, and where the file lives.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct CacheFile<E> {
+    version: u32,
+    entries: HashMap<String, E>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl<E: DeserializeOwned> CacheFile<E> {
+    /// Loads the cache from `path`, starting empty if it is missing, unreadable or outdated.
+    fn load(path: PathBuf) -> Self {
+        let cache = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<CacheFile<E>>(&text).ok())
+            .filter(|cache| cache.version == CACHE_VERSION);
+
+        CacheFile {
+            path: Some(path),
+            ..cache.unwrap_or_default()
+        }
+    }
+}
+
+impl<E: Serialize> CacheFile<E> {
+    /// Writes the cache back to the file it was loaded from.
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.path {
+            Some(path) => write_json(path, self),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<E> Default for CacheFile<E> {
+    fn default() -> Self {
+        CacheFile {
+            version: CACHE_VERSION,
+            entries: HashMap::new(),
+            path: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ResponseCache(CacheFile<CachedResponse>);
+
+impl ResponseCache {
+    /// Loads the cache from `path`, starting empty if it is missing, unreadable or outdated.
+    pub fn load(path: PathBuf) -> Self {
+        ResponseCache(CacheFile::load(path))
+    }
+
+    /// Loads the cache from its default location in the user's cache directory.
+    pub fn load_default() -> Self {
+        Self::load(crate::paths::cache_dir().join("http-cache.json"))
+    }
+
+    pub fn get(&self, url: &str) -> Option<&CachedResponse> {
+        self.0.entries.get(url)
+    }
+
+    pub fn insert(&mut self, url: String, response: CachedResponse) {
+        self.0.entries.insert(url, response);
+    }
+
+    /// Drops the pages unused for longer than `max_age`, then all but the `max_entries` most
+    /// recently used ones.
+    fn prune(&mut self, now: Timestamp, max_age: Duration, max_entries: usize) {
+        let mut entries = std::mem::take(&mut self.0.entries)
+            .into_iter()
+            .filter(|(_, response)| response.used_at.age(now) < max_age)
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(_, response)| std::cmp::Reverse(response.used_at));
+        entries.truncate(max_entries);
+        self.0.entries = entries.into_iter().collect();
+    }
+
+    /// Writes the cache back to the file it was loaded from, leaving out the pages that
+    /// haven't been used in a long time.
+    pub fn save(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.prune(Timestamp::now(), MAX_RESPONSE_AGE, MAX_RESPONSES);
+        self.0.save()
+    }
+}
+
+/// A repository listing as fetched at some point in time.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RepoList {
+    pub fetched_at: Timestamp,
+    pub repos: Vec<Repo>,
+}
+
+/// On-disk cache of unfiltered repository listings, so repeated runs can skip listing
+/// repositories altogether while the listing is young enough.
+#[derive(Debug, Default)]
+pub struct RepoListCache(CacheFile<RepoList>);
+
+impl RepoListCache {
+    /// Loads the cache from `path`, starting empty if it is missing, unreadable or outdated.
+    pub fn load(path: PathBuf) -> Self {
+        RepoListCache(CacheFile::load(path))
+    }
+
+    /// Loads the cache from its default location in the user's cache directory.
+    pub fn load_default() -> Self {
+        Self::load(crate::paths::cache_dir().join("repo-cache.json"))
+    }
+
+    /// The repositories listed under `key`, unless the listing is older than `max_age`.
+    pub fn get(&self, key: &str, max_age: Duration) -> Option<&[Repo]> {
+        self.0
+            .entries
+            .get(key)
+            .filter(|list| list.fetched_at.age(Timestamp::now()) < max_age)
+            .map(|list| list.repos.as_slice())
+    }
+
+    pub fn insert(&mut self, key: String, repos: Vec<Repo>) {
+        let fetched_at = Timestamp::now();
+        self.0.entries.insert(key, RepoList { fetched_at, repos });
+    }
+
+    /// Writes the cache back to the file it was loaded from.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.0.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "issue-roulette-{}-{}.json",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn repo_list_cache_round_trips() {
+        let path = temp_path("repo-cache");
+        let mut cache = RepoListCache::load(path.clone());
+        cache.insert(
+            "user:octocat".to_string(),
+            vec![Repo::named("octocat/a".to_string())],
+        );
+        cache.save().unwrap();
+
+        let cache = RepoListCache::load(path.clone());
+        let repos = cache.get("user:octocat", Duration::from_secs(60)).unwrap();
+        assert_eq!(repos[0].full_name, "octocat/a");
+        assert!(cache.get("user:octocat", Duration::ZERO).is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn outdated_cache_files_are_discarded() {
+        let path = temp_path("outdated-cache");
+        let body = r#"{"version":1,"entries":{"u":{"etag":"e","body":"[]","next":null}}}"#;
+        std::fs::write(&path, body).unwrap();
+        assert!(ResponseCache::load(path.clone()).get("u").is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn response(etag: &str, used_at: Timestamp) -> CachedResponse {
+        CachedResponse {
+            etag: etag.to_string(),
+            body: "[]".to_string(),
+            next: None,
+            used_at,
+        }
+    }
+
+    #[test]
+    fn pruning_drops_stale_and_excess_pages() {
+        let now = Timestamp::from_unix(1_000_000);
+        let ago = |seconds| Timestamp::from_unix(now.unix() - seconds);
+        let mut cache = ResponseCache::default();
+        cache.insert("stale".to_string(), response("s", ago(1000)));
+        cache.insert("old".to_string(), response("o", ago(30)));
+        cache.insert("recent".to_string(), response("r", ago(20)));
+        cache.insert("newest".to_string(), response("n", ago(10)));
+
+        cache.prune(now, Duration::from_secs(100), 2);
+        let mut urls = cache
+            .0
+            .entries
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        urls.sort_unstable();
+        assert_eq!(urls, ["newest", "recent"]);
+    }
+
+    #[test]
+    fn saving_drops_long_unused_pages() {
+        let path = temp_path("response-cache");
+        let mut cache = ResponseCache::load(path.clone());
+        let now = Timestamp::now();
+        cache.insert("fresh".to_string(), response("f", now));
+        cache.insert("stale".to_string(), response("s", now - MAX_RESPONSE_AGE));
+        cache.save().unwrap();
+
+        let cache = ResponseCache::load(path.clone());
+        assert_eq!(cache.get("fresh").unwrap().etag, "f");
+        assert!(cache.get("stale").is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+}
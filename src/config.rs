@@ -0,0 +1,243 @@
+//! Optional TOML configuration file providing defaults for command line flags.
+
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Defaults read from the configuration file. Keys are named like the long command line
+/// flags, with either dashes or underscores, e.g. `include_forked_repos = true`.
+#[derive(serde::Deserialize, Debug, Default)]
+pub struct Config {
+    pub username: Option<String>,
+    pub org: Option<String>,
+    pub repos_file: Option<PathBuf>,
+    pub affiliation: Option<Vec<String>>,
+    pub starred: Option<bool>,
+    pub include_forked_repos: Option<bool>,
+    pub forks_only: Option<bool>,
+    pub include_archived: Option<bool>,
+    pub private_only: Option<bool>,
+    pub public_only: Option<bool>,
+    pub min_issues: Option<u32>,
+    pub min_stars: Option<u32>,
+    pub language: Option<String>,
+    pub topics: Option<Vec<String>>,
+    pub include_repo: Option<Vec<String>>,
+    pub exclude_repo: Option<Vec<String>>,
+    pub token_file: Option<PathBuf>,
+    pub app_id: Option<u64>,
+    pub private_key_file: Option<PathBuf>,
+    pub installation_id: Option<u64>,
+    pub user_agent: Option<String>,
+    pub max_retries: Option<u32>,
+    pub max_pages: Option<u32>,
+    pub base_url: Option<String>,
+    pub state: Option<String>,
+    pub include_prs: Option<bool>,
+    pub exclude_drafts: Option<bool>,
+    pub labels: Option<Vec<String>>,
+    pub exclude_labels: Option<Vec<String>>,
+    pub prefer_labels: Option<Vec<String>>,
+    pub prefer_weight: Option<f64>,
+    pub good_first_issue: Option<bool>,
+    pub format: Option<String>,
+    pub template: Option<String>,
+    pub repo_filter: Option<String>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub count: Option<usize>,
+    pub dedup: Option<bool>,
+    pub shuffle: Option<bool>,
+    pub explain: Option<bool>,
+    pub interactive: Option<bool>,
+    pub count_per_repo: Option<usize>,
+    pub no_color: Option<bool>,
+    pub no_emoji: Option<bool>,
+    pub output: Option<PathBuf>,
+    pub notify_url: Option<String>,
+    pub notify_format: Option<String>,
+    pub log_level: Option<String>,
+    pub verbose: Option<bool>,
+    pub quiet: Option<bool>,
+    pub cache_ttl: Option<String>,
+    pub no_cache: Option<bool>,
+    pub all_issues: Option<bool>,
+    pub max_repos: Option<usize>,
+    pub issues_per_repo: Option<u32>,
+    pub concurrency: Option<usize>,
+    pub no_history: Option<bool>,
+    pub history_days: Option<u64>,
+    pub open: Option<bool>,
+    pub weighted: Option<bool>,
+    pub bias: Option<String>,
+    pub reaction_weight: Option<f64>,
+    pub stale_days: Option<f64>,
+    pub new_only: Option<bool>,
+    pub issue_author: Option<String>,
+    pub milestone: Option<String>,
+    pub no_milestone: Option<bool>,
+    pub min_comments: Option<u32>,
+    pub max_comments: Option<u32>,
+    pub min_reactions: Option<u32>,
+    pub include_assigned: Option<bool>,
+    pub assigned_to: Option<String>,
+    /// Keys that don't correspond to any setting.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, Value>,
+}
+
+impl Config {
+    /// Reads the configuration at `path`.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+        let table = parse_toml(&text)
+            .map_err(|e| format!("Failed to parse config {}: {}", path.display(), e))?;
+        serde_json::from_value(Value::Object(table))
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e).into())
+    }
+
+    /// Reads the configuration from its default location, if a file exists there.
+    pub fn load_default() -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        match default_path() {
+            Some(path) if path.exists() => Self::load(&path).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// `~/.config/issue-roulette/config.toml`, honoring `XDG_CONFIG_HOME`.
+pub fn default_path() -> Option<PathBuf> {
+    crate::paths::config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// Parses the subset of TOML a flat settings file needs: `key = value` pairs whose values
+/// are strings, integers, floats, booleans or single-line arrays of those. Keys below a `[table]`
+/// header are prefixed with its name, so they end up unknown rather than silently applied.
+fn parse_toml(text: &str) -> Result<Map<String, Value>, String> {
+    let mut table = Map::new();
+    let mut prefix = String::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", index + 1, message);
+
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            prefix = format!("{}.", header.trim());
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected `key = value`"))?;
+        let key = key.trim().trim_matches('"').replace('-', "_");
+        let value = parse_value(value.trim()).ok_or_else(|| error("unsupported value"))?;
+        table.insert(format!("{}{}", prefix, key), value);
+    }
+
+    Ok(table)
+}
+
+/// Cuts a trailing `# comment`, leaving `#` inside quoted strings alone.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (index, c) in line.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            ('#', None) => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_value(text: &str) -> Option<Value> {
+    if let Some(items) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        return split_items(items)
+            .into_iter()
+            .map(|item| parse_value(item.trim()))
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Array);
+    }
+    if let Some(literal) = text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+        return Some(Value::String(literal.to_string()));
+    }
+    if text.starts_with('"') {
+        return serde_json::from_str::<String>(text).ok().map(Value::String);
+    }
+    match text {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        _ => {
+            let number = text.replace('_', "");
+            if let Ok(integer) = number.parse::<i64>() {
+                return Some(Value::from(integer));
+            }
+            // JSON has no room for inf and nan, which no setting needs anyway.
+            let float = number.parse::<f64>().ok()?;
+            serde_json::Number::from_f64(float).map(Value::Number)
+        }
+    }
+}
+
+/// Splits array items on commas outside of quotes, dropping a trailing empty item.
+fn split_items(items: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (index, c) in items.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            (',', None) => {
+                parts.push(&items[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&items[start..]);
+    parts.retain(|part| !part.trim().is_empty());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Config {
+        serde_json::from_value(Value::Object(parse_toml(text).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn parses_floats() {
+        let config = parse("stale_days = 7.5\nreaction_weight = 0.25\n");
+        assert_eq!(config.stale_days, Some(7.5));
+        assert_eq!(config.reaction_weight, Some(0.25));
+    }
+
+    #[test]
+    fn parses_prefer_weight() {
+        let config = parse("prefer_labels = [\"good first issue\"]\nprefer_weight = 1.5\n");
+        assert_eq!(
+            config.prefer_labels,
+            Some(vec!["good first issue".to_string()])
+        );
+        assert_eq!(config.prefer_weight, Some(1.5));
+    }
+
+    #[test]
+    fn parses_integers_for_float_settings() {
+        assert_eq!(parse("stale_days = 7").stale_days, Some(7.0));
+    }
+
+    #[test]
+    fn rejects_non_finite_floats() {
+        assert_eq!(parse_value("inf"), None);
+        assert_eq!(parse_value("nan"), None);
+    }
+}
@@ -0,0 +1,40 @@
+//! API responses for tests, in the shape the REST API returns them. Shared by the unit tests
+//! of the library and the binary and by the integration tests, so it uses nothing of the crate
+//! itself.
+#![allow(dead_code)] // Not every test uses every fixture.
+
+use serde_json::{json, Value};
+
+/// An open issue of `repo` carrying labels with these names.
+pub fn issue_json(repo: &str, number: u32, title: &str, labels: &[&str]) -> Value {
+    json!({
+        "title": title,
+        "number": number,
+        "html_url": format!("https://github.com/{}/issues/{}", repo, number),
+        "repository_url": format!("https://api.github.com/repos/{}", repo),
+        "state": "open",
+        "labels": labels
+            .iter()
+            .map(|name| json!({ "name": name, "color": "d73a4a" }))
+            .collect::<Vec<_>>(),
+        "assignees": [],
+        "comments": 0,
+        "user": { "login": "octocat" },
+        "milestone": null,
+    })
+}
+
+/// A public repository that has issues enabled and one open.
+pub fn repo_json(full_name: &str) -> Value {
+    json!({
+        "full_name": full_name,
+        "fork": false,
+        "archived": false,
+        "private": false,
+        "has_issues": true,
+        "open_issues": 1,
+        "stargazers_count": 0,
+        "language": null,
+        "topics": [],
+    })
+}
@@ -0,0 +1,58 @@
+//! Shell-style wildcard matching for repository names.
+
+/// Matches `text` against `pattern`, where `*` stands for any run of characters (including
+/// `/`) and `?` for a single character. Comparison ignores ASCII case, like GitHub names do.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` seen and the text position it was tried at.
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c.eq_ignore_ascii_case(&text[t]) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_owner_and_name() {
+        assert!(glob_match("*/legacy-*", "acme/legacy-api"));
+        assert!(glob_match("*/legacy-*", "octo/legacy-"));
+        assert!(!glob_match("*/legacy-*", "acme/api-legacy"));
+        assert!(!glob_match("*/legacy-*", "legacy-api"));
+    }
+
+    #[test]
+    fn star_spans_slashes() {
+        assert!(glob_match("acme*", "acme/tools"));
+        assert!(glob_match("*", "acme/tools"));
+    }
+
+    #[test]
+    fn question_mark_and_case() {
+        assert!(glob_match("acme/tool?", "ACME/Tools"));
+        assert!(!glob_match("acme/tool?", "acme/tool"));
+    }
+}
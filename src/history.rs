@@ -0,0 +1,133 @@
+//! Persistent record of recently chosen issues, so repeated runs don't serve the same ones.
+
+use crate::paths::write_json;
+use crate::Timestamp;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// Bumped whenever the file layout changes; files with another version are discarded.
+const HISTORY_VERSION: u32 = 1;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub url: String,
+    pub picked_at: Timestamp,
+}
+
+/// Chosen issues grouped by context, e.g. the user or organization that was rolled on.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct History {
+    version: u32,
+    contexts: HashMap<String, Vec<HistoryEntry>>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// Loads the history from `path`, starting empty if it is missing, unreadable or outdated.
+    pub fn load(path: PathBuf) -> Self {
+        let history = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<History>(&text).ok())
+            .filter(|history| history.version == HISTORY_VERSION);
+
+        History {
+            path: Some(path),
+            ..history.unwrap_or_default()
+        }
+    }
+
+    /// Loads the history from its default location in the user's state directory.
+    pub fn load_default() -> Self {
+        Self::load(crate::paths::state_dir().join("history.json"))
+    }
+
+    /// URLs of the issues picked in `context` at or after `since`.
+    pub fn recent(&self, context: &str, since: Timestamp) -> HashSet<&str> {
+        self.contexts
+            .get(context)
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.picked_at >= since)
+            .map(|entry| entry.url.as_str())
+            .collect()
+    }
+
+    pub fn record(&mut self, context: &str, url: String, picked_at: Timestamp) {
+        let entries = self.contexts.entry(context.to_string()).or_default();
+        entries.retain(|entry| entry.url != url);
+        entries.push(HistoryEntry { url, picked_at });
+    }
+
+    /// Drops all entries of `context` picked before `before`.
+    pub fn prune(&mut self, context: &str, before: Timestamp) {
+        if let Some(entries) = self.contexts.get_mut(context) {
+            entries.retain(|entry| entry.picked_at >= before);
+        }
+    }
+
+    pub fn forget(&mut self, context: &str) {
+        self.contexts.remove(context);
+    }
+
+    /// Writes the history back to the file it was loaded from.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.path {
+            Some(path) => write_json(path, self),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            version: HISTORY_VERSION,
+            contexts: HashMap::new(),
+            path: None,
+        }
+    }
+}
+
+/// When each context was last rolled on with `--new-only`, kept next to the history.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+pub struct LastRuns {
+    contexts: HashMap<String, Timestamp>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl LastRuns {
+    /// Loads the timestamps from `path`, starting empty if it is missing or unreadable.
+    pub fn load(path: PathBuf) -> Self {
+        let last_runs = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<LastRuns>(&text).ok());
+
+        LastRuns {
+            path: Some(path),
+            ..last_runs.unwrap_or_default()
+        }
+    }
+
+    /// Loads the timestamps from their default location in the user's state directory.
+    pub fn load_default() -> Self {
+        Self::load(crate::paths::state_dir().join("last-run.json"))
+    }
+
+    pub fn get(&self, context: &str) -> Option<Timestamp> {
+        self.contexts.get(context).copied()
+    }
+
+    pub fn record(&mut self, context: &str, ran_at: Timestamp) {
+        self.contexts.insert(context.to_string(), ran_at);
+    }
+
+    /// Writes the timestamps back to the file they were loaded from.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.path {
+            Some(path) => write_json(path, self),
+            None => Ok(()),
+        }
+    }
+}
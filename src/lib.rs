@@ -0,0 +1,1689 @@
+//! Fetching and filtering of GitHub repositories and issues for issue-roulette.
+
+use futures_util::stream::{self, StreamExt};
+use rand::Rng;
+use reqwest::{
+    header::{HeaderMap, HeaderValue},
+    StatusCode,
+};
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+mod app;
+mod cache;
+mod config;
+#[cfg(test)]
+mod fixtures;
+mod glob;
+mod graphql;
+mod history;
+mod logger;
+mod paths;
+mod picker;
+mod progress;
+mod recording;
+mod repo_filter;
+mod template;
+#[cfg(test)]
+mod test_server;
+#[cfg(test)]
+mod testing;
+mod throttle;
+mod time;
+
+pub use app::{create_installation_token, AppCredentials, InstallationToken};
+pub use cache::{CachedResponse, RepoList, RepoListCache, ResponseCache};
+pub use config::Config;
+pub use glob::glob_match;
+pub use graphql::{get_repos_with_issues, MAX_GRAPHQL_ISSUES_PER_REPO};
+pub use history::{History, HistoryEntry, LastRuns};
+pub use logger::{init_logger, parse_log_directives};
+pub use picker::{pick, Pick};
+pub use progress::Spinner;
+pub use recording::Recording;
+pub use repo_filter::RepoFilterExpr;
+pub use template::Template;
+pub use time::{parse_duration, Timestamp};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct Repo {
+    pub full_name: String,
+    pub fork: bool,
+    pub archived: bool,
+    pub private: bool,
+    pub has_issues: bool,
+    pub open_issues: u32,
+    pub stargazers_count: u32,
+    /// The primary language GitHub detected, if any.
+    pub language: Option<String>,
+    /// Older GitHub Enterprise servers leave topics out of repository listings.
+    #[serde(default)]
+    pub topics: Vec<String>,
+}
+
+impl Repo {
+    /// A repository of which only the name is known, such as the source of a search result.
+    pub fn named(full_name: String) -> Self {
+        Repo {
+            full_name,
+            has_issues: true,
+            ..Repo::default()
+        }
+    }
+
+    pub fn has_topic(&self, topic: &str) -> bool {
+        self.topics.iter().any(|t| t.eq_ignore_ascii_case(topic))
+    }
+}
+
+impl std::fmt::Display for Repo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.full_name)
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct Issue {
+    pub title: String,
+    pub number: u32,
+    pub html_url: String,
+    pub repository_url: String,
+    pub state: String,
+    /// Present only when the "issue" is actually a pull request.
+    pub pull_request: Option<serde_json::Value>,
+    /// Whether a pull request is a draft. Only sent for pull requests.
+    #[serde(default)]
+    pub draft: bool,
+    pub labels: Vec<Label>,
+    pub created_at: Option<Timestamp>,
+    pub updated_at: Option<Timestamp>,
+    pub assignees: Vec<User>,
+    pub comments: u32,
+    /// The user who opened the issue.
+    pub user: User,
+    pub milestone: Option<Milestone>,
+    /// Missing from responses of older GitHub Enterprise Server versions.
+    #[serde(default)]
+    pub reactions: Reactions,
+}
+
+impl Issue {
+    pub fn is_assigned_to(&self, login: &str) -> bool {
+        self.assignees
+            .iter()
+            .any(|user| user.login.eq_ignore_ascii_case(login))
+    }
+
+    /// The owner/name of the repository the issue belongs to.
+    pub fn repository_name(&self) -> &str {
+        let path = self.repository_url.split("/repos/").last();
+        path.unwrap_or(&self.repository_url)
+    }
+
+    pub fn has_label(&self, name: &str) -> bool {
+        self.labels
+            .iter()
+            .any(|label| label.name.eq_ignore_ascii_case(name))
+    }
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {} -> {}",
+            self.number,
+            sanitize(&self.title),
+            self.html_url
+        )
+    }
+}
+
+/// Makes untrusted text such as issue titles safe to print to a terminal: line breaks and
+/// tabs become spaces, other control characters and bidirectional overrides, which could
+/// move the cursor or reorder what follows, are shown escaped like `\u{202e}`.
+pub fn sanitize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\t' | '\n' | '\r' => out.push(' '),
+            '\u{200e}'
+            | '\u{200f}'
+            | '\u{061c}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2066}'..='\u{2069}' => out.extend(c.escape_unicode()),
+            c if c.is_control() => out.extend(c.escape_unicode()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct Label {
+    pub name: String,
+    /// The label's color as six hex digits without `#`, e.g. `d73a4a`.
+    #[serde(default)]
+    pub color: String,
+}
+
+impl Label {
+    /// The label's color as red, green and blue, if it is valid hex.
+    pub fn rgb(&self) -> Option<(u8, u8, u8)> {
+        let hex = self.color.trim_start_matches('#');
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |range| u8::from_str_radix(&hex[range], 16).ok();
+        Some((channel(0..2)?, channel(2..4)?, channel(4..6)?))
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct User {
+    pub login: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct Milestone {
+    pub title: String,
+    pub number: u32,
+}
+
+/// The reactions to an issue, of which only the total is kept.
+#[derive(serde::Deserialize, Debug, Default)]
+pub struct Reactions {
+    pub total_count: u32,
+}
+
+impl Milestone {
+    /// Whether `name` is the number or, ignoring case, the title of this milestone.
+    pub fn matches(&self, name: &str) -> bool {
+        name.parse() == Ok(self.number) || self.title.eq_ignore_ascii_case(name)
+    }
+}
+
+/// A GitHub API client together with the state shared between its requests.
+pub struct GitHub {
+    http: reqwest::Client,
+    base_url: String,
+    cache: Option<Mutex<ResponseCache>>,
+    verbose: bool,
+    timeout: Duration,
+    max_retries: u32,
+    max_pages: u32,
+    throttle: throttle::Throttle,
+    /// The token's user and scopes, looked up at most once.
+    token_info: tokio::sync::OnceCell<TokenInfo>,
+    /// Set when acting as a GitHub App installation, whose tokens are refreshed as they expire.
+    app: Option<app::AppAuth>,
+    tape: Option<Tape>,
+}
+
+/// Where responses are recorded to or replayed from.
+enum Tape {
+    Record(Mutex<Recording>, PathBuf),
+    Replay(Recording),
+}
+
+/// How long a request may take by default, from connecting until the body is read.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a request failing with a 502, 503 or 504 or a dropped connection is retried by
+/// default.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// How many pages of a listing are fetched at most by default.
+pub const DEFAULT_MAX_PAGES: u32 = 100;
+
+/// How many requests are in flight at most by default.
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Root of the public GitHub REST API.
+pub const DEFAULT_BASE_URL: &str = "https://api.github.com";
+
+impl GitHub {
+    /// Wraps `http` to talk to the public GitHub API without a response cache.
+    pub fn new(http: reqwest::Client) -> Self {
+        GitHub {
+            http,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            cache: None,
+            verbose: false,
+            timeout: DEFAULT_TIMEOUT,
+            max_retries: DEFAULT_MAX_RETRIES,
+            max_pages: DEFAULT_MAX_PAGES,
+            throttle: throttle::Throttle::new(DEFAULT_CONCURRENCY),
+            token_info: tokio::sync::OnceCell::new(),
+            app: None,
+            tape: None,
+        }
+    }
+
+    /// Limits how long each request may take, from connecting until the body is read.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Retries requests failing with a 502, 503 or 504 or a dropped connection up to
+    /// `max_retries` times, waiting longer after each attempt.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sends up to `concurrency` requests at once, fewer as the rate limit runs low.
+    pub fn with_concurrency(self, concurrency: usize) -> Self {
+        self.throttle.set_max(concurrency);
+        self
+    }
+
+    /// Stops following `next` links after `max_pages` pages of a listing, guarding against
+    /// servers that paginate endlessly.
+    pub fn with_max_pages(mut self, max_pages: u32) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// Authenticates as a GitHub App installation with `token`, replacing it with a fresh one
+    /// minted from `credentials` before it expires.
+    pub fn with_app(mut self, credentials: AppCredentials, token: InstallationToken) -> Self {
+        self.app = Some(app::AppAuth::new(credentials, token));
+        self
+    }
+
+    /// Whether the client acts as a GitHub App installation rather than as a user.
+    pub fn is_app(&self) -> bool {
+        self.app.is_some()
+    }
+
+    /// Keeps every response in memory for [`GitHub::save_recording`] to write to `path`.
+    pub fn with_recording(mut self, path: PathBuf) -> Self {
+        self.tape = Some(Tape::Record(Mutex::default(), path));
+        self
+    }
+
+    /// Answers every request from `recording` instead of sending it.
+    pub fn with_replay(mut self, recording: Recording) -> Self {
+        self.tape = Some(Tape::Replay(recording));
+        self
+    }
+
+    /// Writes the responses kept since [`GitHub::with_recording`], if recording.
+    pub fn save_recording(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.tape {
+            Some(Tape::Record(recording, path)) => recording.lock().unwrap().save(path),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sends `req` with the configured timeout, reporting timeouts as [`TimeoutError`]. Waits
+    /// while too many requests are in flight for the remaining rate limit.
+    async fn send(
+        &self,
+        req: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+        let mut request = req.timeout(self.timeout).build()?;
+        if let Some(Tape::Replay(recording)) = &self.tape {
+            return recording.replay(&request);
+        }
+        if let Some(app) = &self.app {
+            let token = app.header(&self.http, &self.base_url, self.timeout).await?;
+            request
+                .headers_mut()
+                .insert(reqwest::header::AUTHORIZATION, token);
+        }
+        let key = Recording::key(&request);
+        let _permit = self.throttle.acquire().await?;
+        let res = self.http.execute(request).await.map_err(|e| {
+            if e.is_timeout() {
+                Box::new(TimeoutError(self.timeout)) as Box<dyn std::error::Error>
+            } else {
+                Box::new(e)
+            }
+        })?;
+        self.throttle.observe(res.headers());
+        match &self.tape {
+            Some(Tape::Record(recording, _)) => Recording::record(recording, key, res).await,
+            _ => Ok(res),
+        }
+    }
+
+    /// Logs every response together with the remaining rate limit budget to stderr.
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Targets another API root, such as `https://<host>/api/v3` for GitHub Enterprise Server.
+    /// The URL is expected without a trailing slash, see [`parse_base_url`].
+    pub fn with_base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    /// Answers unchanged pages from `cache` via conditional requests.
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(Mutex::new(cache));
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// Persists the response cache, if one is in use.
+    pub fn save_cache(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.cache {
+            Some(cache) => cache.lock().map_err(|e| e.to_string())?.save(),
+            None => Ok(()),
+        }
+    }
+
+    fn cached_response(&self, url: &str) -> Option<CachedResponse> {
+        self.cache.as_ref()?.lock().ok()?.get(url).cloned()
+    }
+
+    fn cache_response(&self, url: &str, response: CachedResponse) {
+        if let Some(mut cache) = self.cache.as_ref().and_then(|cache| cache.lock().ok()) {
+            cache.insert(url.to_string(), response);
+        }
+    }
+}
+
+/// Validates an API root URL given on the command line and strips any trailing slash.
+pub fn parse_base_url(url: &str) -> Result<String, String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid URL '{}': {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(format!("'{}' is not an http(s) URL", url));
+    }
+    Ok(url.trim_end_matches('/').to_string())
+}
+
+/// Transport settings for [`build_http_client`].
+#[derive(Debug, Clone)]
+pub struct HttpOptions {
+    /// How long establishing a connection may take.
+    pub connect_timeout: Duration,
+    /// Proxy for all requests. Without one, `HTTPS_PROXY`/`HTTP_PROXY`/`NO_PROXY` apply.
+    pub proxy: Option<String>,
+    pub user_agent: String,
+}
+
+/// Sent as `User-Agent` unless overridden, so the version shows up in server logs.
+pub const DEFAULT_USER_AGENT: &str = concat!("issue-roulette/", env!("CARGO_PKG_VERSION"));
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        HttpOptions {
+            connect_timeout: Duration::from_secs(10),
+            proxy: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+        }
+    }
+}
+
+pub fn build_http_client(
+    token: &Option<HeaderValue>,
+    options: &HttpOptions,
+) -> Result<reqwest::Client, Box<dyn std::error::Error>> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Accept",
+        HeaderValue::from_static("application/vnd.github+json"),
+    );
+    headers.insert(
+        "X-Github-Api-Version",
+        HeaderValue::from_static("2022-11-28"),
+    );
+
+    if let Some(token) = token {
+        headers.insert(reqwest::header::AUTHORIZATION, token.clone());
+    }
+
+    let mut builder = reqwest::Client::builder()
+        .user_agent(&options.user_agent)
+        .default_headers(headers)
+        .connect_timeout(options.connect_timeout);
+
+    if let Some(proxy) = &options.proxy {
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| format!("Invalid proxy URL '{}': {}", proxy, e))?
+            .no_proxy(reqwest::NoProxy::from_env());
+        builder = builder.proxy(proxy);
+    }
+
+    Ok(builder.build()?)
+}
+
+#[derive(Debug, Clone)]
+pub struct BadRequestError(pub u16, pub String);
+impl std::fmt::Display for BadRequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}]: {}", self.0, self.1)
+    }
+}
+impl std::error::Error for BadRequestError {}
+
+#[derive(Debug, Clone)]
+pub struct BadCredentialsError;
+impl std::fmt::Display for BadCredentialsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Bad credentials, check that your token is valid and not expired."
+        )
+    }
+}
+impl std::error::Error for BadCredentialsError {}
+
+/// An error prefixed with what was being done when it occurred. Unlike formatting it into a
+/// string, this keeps the original error reachable through `source()`.
+#[derive(Debug)]
+pub struct ContextError {
+    context: String,
+    source: Box<dyn std::error::Error>,
+}
+impl ContextError {
+    pub fn new(context: impl Into<String>, source: Box<dyn std::error::Error>) -> Self {
+        ContextError {
+            context: context.into(),
+            source,
+        }
+    }
+}
+impl std::fmt::Display for ContextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.context, self.source)
+    }
+}
+impl std::error::Error for ContextError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Turns an unsuccessful response into an error, singling out rejected tokens.
+fn error_for_status(status: StatusCode, text: String) -> Box<dyn std::error::Error> {
+    match status {
+        StatusCode::UNAUTHORIZED => Box::new(BadCredentialsError),
+        _ => Box::new(BadRequestError(status.as_u16(), text)),
+    }
+}
+
+/// Replaces a `404 Not Found` error with `message`, leaving other errors untouched.
+fn map_not_found(e: Box<dyn std::error::Error>, message: String) -> Box<dyn std::error::Error> {
+    match e.downcast_ref::<BadRequestError>() {
+        Some(BadRequestError(404, _)) => message.into(),
+        _ => e,
+    }
+}
+
+/// GitHub answers `410 Gone` for the issues of a repository that has them disabled, which the
+/// repository listing may not reflect yet.
+#[derive(Debug, Clone)]
+pub struct IssuesDisabledError(pub String);
+impl std::fmt::Display for IssuesDisabledError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Issues are disabled for {}.", self.0)
+    }
+}
+impl std::error::Error for IssuesDisabledError {}
+
+/// A repository that is listed but whose issues the token may not read, typically because an
+/// organization enforces SAML SSO and the token isn't authorized for it.
+#[derive(Debug, Clone)]
+pub struct IssuesForbiddenError(pub String);
+impl std::fmt::Display for IssuesForbiddenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The token may not read the issues of {}, is it authorized for the organization's SSO?",
+            self.0
+        )
+    }
+}
+impl std::error::Error for IssuesForbiddenError {}
+
+#[derive(Debug, Clone)]
+pub struct TimeoutError(pub Duration);
+impl std::fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Request timed out after {}s.", self.0.as_secs_f64())
+    }
+}
+impl std::error::Error for TimeoutError {}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitedError(pub Duration);
+impl std::fmt::Display for RateLimitedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "GitHub rate limit exceeded, try again in {}s. Supplying a token raises the limit.",
+            self.0.as_secs()
+        )
+    }
+}
+impl std::error::Error for RateLimitedError {}
+
+#[derive(Debug, Clone)]
+pub struct SecondaryRateLimitedError(pub Duration);
+impl std::fmt::Display for SecondaryRateLimitedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "GitHub's secondary rate limit was hit, try again in {}s with a lower --concurrency.",
+            self.0.as_secs()
+        )
+    }
+}
+impl std::error::Error for SecondaryRateLimitedError {}
+
+/// How often a rate limited request is attempted before giving up.
+const MAX_ATTEMPTS: u32 = 3;
+/// Longest delay between attempts when GitHub doesn't say how long to wait.
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+/// Rate limits resetting further in the future than this are reported instead of awaited.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+/// Secondary rate limits get a single retry, since retrying early prolongs them.
+const MAX_SECONDARY_ATTEMPTS: u32 = 2;
+/// GitHub asks to wait at least a minute after a secondary rate limit.
+const SECONDARY_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// How the authenticated user is related to a repository.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Affiliation {
+    Owner,
+    Collaborator,
+    OrganizationMember,
+    /// Shorthand for all of the above.
+    All,
+}
+
+impl Affiliation {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Affiliation::Owner => "owner",
+            Affiliation::Collaborator => "collaborator",
+            Affiliation::OrganizationMember => "organization_member",
+            Affiliation::All => "owner,collaborator,organization_member",
+        }
+    }
+}
+
+/// Lists the repositories the token can access through any of `affiliations`.
+pub async fn get_all_repos(
+    client: &GitHub,
+    affiliations: &[Affiliation],
+) -> Result<Vec<Repo>, Box<dyn std::error::Error>> {
+    let mut url = client.url("/user/repos?per_page=100");
+    if !affiliations.is_empty() {
+        let affiliations = affiliations
+            .iter()
+            .map(|affiliation| affiliation.as_str())
+            .collect::<Vec<_>>();
+        url.push_str("&affiliation=");
+        url.push_str(&affiliations.join(","));
+    }
+    fetch_all_pages(client, url).await
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct InstallationRepos {
+    repositories: Vec<Repo>,
+}
+
+/// Lists the repositories a GitHub App installation was granted, see [`GitHub::with_app`].
+/// Installation tokens have no user, so [`get_all_repos`] doesn't work for them.
+pub async fn get_installation_repos(
+    client: &GitHub,
+) -> Result<Vec<Repo>, Box<dyn std::error::Error>> {
+    let url = client.url("/installation/repositories?per_page=100");
+    fetch_pages(client, url, None, |page: InstallationRepos| {
+        page.repositories
+    })
+    .await
+}
+
+pub async fn get_public_repos(
+    client: &GitHub,
+    username: &str,
+) -> Result<Vec<Repo>, Box<dyn std::error::Error>> {
+    fetch_all_pages(
+        client,
+        client.url(&format!("/users/{}/repos?per_page=100", username)),
+    )
+    .await
+    .map_err(|e| {
+        let message = format!("User '{}' not found, check the spelling.", username);
+        map_not_found(e, message)
+    })
+}
+
+/// Lists the repositories starred by `username`, or by the authenticated user if `None`.
+pub async fn get_starred_repos(
+    client: &GitHub,
+    username: Option<&str>,
+) -> Result<Vec<Repo>, Box<dyn std::error::Error>> {
+    let Some(username) = username else {
+        return fetch_all_pages(client, client.url("/user/starred?per_page=100")).await;
+    };
+    fetch_all_pages(
+        client,
+        client.url(&format!("/users/{}/starred?per_page=100", username)),
+    )
+    .await
+    .map_err(|e| {
+        let message = format!("User '{}' not found, check the spelling.", username);
+        map_not_found(e, message)
+    })
+}
+
+pub async fn get_org_repos(
+    client: &GitHub,
+    org: &str,
+) -> Result<Vec<Repo>, Box<dyn std::error::Error>> {
+    fetch_all_pages(
+        client,
+        client.url(&format!("/orgs/{}/repos?per_page=100", org)),
+    )
+    .await
+    .map_err(|e| {
+        let message = format!("Organization '{}' not found, check the spelling.", org);
+        map_not_found(e, message)
+    })
+}
+
+/// Requests `url` and every page linked from it, concatenating the results.
+/// Stops as soon as a page is empty or carries no `rel="next"` link, and with a warning after
+/// [`GitHub::with_max_pages`] pages or when a `next` link leads back to a page already fetched.
+pub async fn fetch_all_pages<T: DeserializeOwned>(
+    client: &GitHub,
+    url: String,
+) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+    fetch_pages(client, url, None, |page: Vec<T>| page).await
+}
+
+/// Like [`fetch_all_pages`] for endpoints whose pages wrap their items, with `items_of`
+/// extracting them from each page of type `P`. With a `limit` no further pages are requested
+/// once that many items arrived, and the result holds at most that many.
+async fn fetch_pages<P: DeserializeOwned, T>(
+    client: &GitHub,
+    url: String,
+    limit: Option<usize>,
+    mut items_of: impl FnMut(P) -> Vec<T>,
+) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+    let mut items = Vec::new();
+    let mut seen = HashSet::new();
+    let mut next = Some(url);
+
+    while let Some(url) = next {
+        if seen.len() == client.max_pages as usize {
+            log::warn!(
+                "Stopped after {} pages, not fetching {}",
+                client.max_pages,
+                url
+            );
+            break;
+        }
+        if !seen.insert(url.clone()) {
+            log::warn!("Stopped paginating, page {} was already fetched", url);
+            break;
+        }
+        let cached = client.cached_response(&url);
+        let etag = cached.as_ref().map(|cached| cached.etag.as_str());
+        let res = send_with_retry(client, &url, etag).await?;
+
+        let body = match (res.status(), cached) {
+            (StatusCode::NOT_MODIFIED, Some(cached)) => {
+                next = cached.next.clone();
+                let body = cached.body.clone();
+                // Still current, so kept from being pruned.
+                let used_at = Timestamp::now();
+                client.cache_response(&url, CachedResponse { used_at, ..cached });
+                body
+            }
+            (StatusCode::OK, _) => {
+                next = next_page_url(res.headers());
+                let etag = res
+                    .headers()
+                    .get(reqwest::header::ETAG)
+                    .and_then(|etag| etag.to_str().ok())
+                    .map(str::to_string);
+                let body = res.text().await?;
+                if let Some(etag) = etag {
+                    let response = CachedResponse {
+                        etag,
+                        body: body.clone(),
+                        next: next.clone(),
+                        used_at: Timestamp::now(),
+                    };
+                    client.cache_response(&url, response);
+                }
+                body
+            }
+            (status, _) => {
+                let text = res.text().await?;
+                return Err(error_for_status(status, text));
+            }
+        };
+
+        let page = items_of(serde_json::from_str::<P>(&body)?);
+        if page.is_empty() {
+            break;
+        }
+        items.extend(page);
+        if let Some(limit) = limit.filter(|limit| items.len() >= *limit) {
+            items.truncate(limit);
+            break;
+        }
+    }
+
+    Ok(items)
+}
+
+/// Sends a GET request to `url`, retrying with capped exponential backoff while rate limited
+/// or after a transient failure, see [`GitHub::with_max_retries`]. With an `etag` the request
+/// is conditional and may be answered with `304 Not Modified`. Forbidden responses other than
+/// rate limits are turned into errors.
+async fn send_with_retry(
+    client: &GitHub,
+    url: &str,
+    etag: Option<&str>,
+) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+    let mut attempt = 1;
+    let mut retries = 0;
+    loop {
+        let mut req = client.http.get(url);
+        if let Some(etag) = etag {
+            req = req.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        let res = client.send(req).await;
+        let failure = match &res {
+            Ok(res) if is_transient_status(res.status()) => Some(res.status().to_string()),
+            Err(e) if is_connection_error(e.as_ref()) => Some(e.to_string()),
+            _ => None,
+        };
+        if let Some(failure) = failure.filter(|_| retries < client.max_retries) {
+            retries += 1;
+            let wait = jittered_backoff(retries);
+            log::warn!(
+                "GET {} failed with {}, retry {} of {} in {:.1}s",
+                url,
+                failure,
+                retries,
+                client.max_retries,
+                wait.as_secs_f64()
+            );
+            tokio::time::sleep(wait).await;
+            continue;
+        }
+        let res = res?;
+        log::debug!("GET {} -> {}", url, res.status());
+        if client.verbose {
+            log_response("GET", url, &res);
+        }
+        let status = res.status();
+        if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+            return Ok(res);
+        }
+
+        let primary = is_rate_limited(&res);
+        let headers = res.headers().clone();
+        let text = res.text().await?;
+        let wait = if is_secondary_rate_limit(&text) {
+            // From now on requests take turns instead of running concurrently.
+            if client.throttle.set_max(1) > 1 {
+                log::warn!("Hit a secondary rate limit, sending one request at a time");
+            }
+            let wait = retry_after(&headers)
+                .unwrap_or(SECONDARY_RATE_LIMIT_WAIT)
+                .max(SECONDARY_RATE_LIMIT_WAIT);
+            if attempt >= MAX_SECONDARY_ATTEMPTS || wait > MAX_RATE_LIMIT_WAIT {
+                return Err(Box::new(SecondaryRateLimitedError(wait)));
+            }
+            wait
+        } else if primary {
+            let backoff = MAX_BACKOFF.min(Duration::from_secs(1 << attempt));
+            let wait = rate_limit_wait(&headers).unwrap_or(backoff);
+            if attempt >= MAX_ATTEMPTS || wait > MAX_RATE_LIMIT_WAIT {
+                return Err(Box::new(RateLimitedError(wait)));
+            }
+            wait
+        } else {
+            return Err(error_for_status(status, text));
+        };
+
+        tokio::time::sleep(wait).await;
+        attempt += 1;
+    }
+}
+
+fn log_response(method: &str, url: &str, res: &reqwest::Response) {
+    let header = |name| {
+        res.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("?")
+    };
+    let reset = header("X-RateLimit-Reset")
+        .parse::<i64>()
+        .map(|reset| Timestamp::from_unix(reset).to_string())
+        .unwrap_or_else(|_| "?".to_string());
+    eprintln!(
+        "{} {} -> {} (rate limit: {} remaining, resets {})",
+        method,
+        url,
+        res.status(),
+        header("X-RateLimit-Remaining"),
+        reset
+    );
+}
+
+/// Gateway errors GitHub answers with while overloaded or deploying, which usually pass.
+fn is_transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Whether `error` is a refused or reset connection. Timeouts aren't retried, as each attempt
+/// would take the full timeout again.
+fn is_connection_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .is_some_and(|e| e.is_connect() || e.is_request())
+}
+
+/// Exponential backoff from half a second up to [`MAX_BACKOFF`], randomized so concurrent
+/// requests don't all retry at once.
+fn jittered_backoff(retry: u32) -> Duration {
+    let backoff = MAX_BACKOFF.min(Duration::from_millis(250 << retry.min(8)));
+    backoff.mul_f64(rand::thread_rng().gen_range(0.5..1.5))
+}
+
+fn is_rate_limited(res: &reqwest::Response) -> bool {
+    match res.status() {
+        StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::FORBIDDEN => {
+            let headers = res.headers();
+            headers.contains_key(reqwest::header::RETRY_AFTER)
+                || headers
+                    .get("X-RateLimit-Remaining")
+                    .is_some_and(|remaining| remaining == "0")
+        }
+        _ => false,
+    }
+}
+
+/// Whether a forbidden response body is GitHub's complaint about a secondary rate limit,
+/// which older servers still call abuse detection.
+fn is_secondary_rate_limit(text: &str) -> bool {
+    let text = text.to_ascii_lowercase();
+    text.contains("secondary rate limit") || text.contains("abuse detection")
+}
+
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse::<u64>()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Reads how long GitHub asks us to wait from `Retry-After` or `X-RateLimit-Reset`.
+fn rate_limit_wait(headers: &HeaderMap) -> Option<Duration> {
+    if let Some(wait) = retry_after(headers) {
+        return Some(wait);
+    }
+
+    let header = |name| headers.get(name)?.to_str().ok()?.parse::<u64>().ok();
+    let reset = header("X-RateLimit-Reset")?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(Duration::from_secs(reset.saturating_sub(now)))
+}
+
+/// Extracts the `rel="next"` target from a GitHub `Link` pagination header.
+fn next_page_url(headers: &HeaderMap) -> Option<String> {
+    let link = headers.get(reqwest::header::LINK)?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let (url, params) = part.split_once(';')?;
+        let is_next = params
+            .split(';')
+            .any(|param| param.trim() == "rel=\"next\"");
+        is_next.then(|| {
+            url.trim()
+                .trim_matches(|c| c == '<' || c == '>')
+                .to_string()
+        })
+    })
+}
+
+pub async fn get_repo(
+    client: &GitHub,
+    full_name: &str,
+) -> Result<Repo, Box<dyn std::error::Error>> {
+    let url = client.url(&format!("/repos/{}", full_name));
+    let res = send_with_retry(client, &url, None).await?;
+
+    match res.status() {
+        StatusCode::OK => Ok(res.json::<Repo>().await?),
+        StatusCode::NOT_FOUND => Err(format!("Repository '{}' not found.", full_name).into()),
+        status => {
+            let text = res.text().await?;
+            Err(error_for_status(status, text))
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IssueState {
+    #[default]
+    Open,
+    Closed,
+    All,
+}
+
+impl IssueState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IssueState::Open => "open",
+            IssueState::Closed => "closed",
+            IssueState::All => "all",
+        }
+    }
+}
+
+/// Which issues of a repository to fetch.
+#[derive(Debug, Clone, Default)]
+pub struct IssueQuery {
+    pub state: IssueState,
+    /// Only issues updated at or after this time.
+    pub since: Option<Timestamp>,
+    /// Stop paginating after this many issues, the most recently created ones.
+    pub limit: Option<usize>,
+}
+
+pub async fn get_issues(
+    client: &GitHub,
+    repo: &Repo,
+    query: &IssueQuery,
+) -> Result<Vec<Issue>, Box<dyn std::error::Error>> {
+    if !repo.has_issues {
+        return Err(Box::new(IssuesDisabledError(repo.full_name.clone())));
+    }
+    let mut url = client.url(&format!(
+        "/repos/{}/issues?per_page=100&state={}",
+        repo.full_name,
+        query.state.as_str()
+    ));
+    if let Some(since) = query.since {
+        url.push_str(&format!("&since={}", since));
+    }
+    let issues: Vec<Issue> = fetch_pages(client, url, query.limit, |page: Vec<Issue>| page)
+        .await
+        .map_err(|e| match e.downcast_ref::<BadRequestError>() {
+            Some(BadRequestError(410, _)) => {
+                Box::new(IssuesDisabledError(repo.full_name.clone())) as Box<dyn std::error::Error>
+            }
+            Some(BadRequestError(403, _)) => Box::new(IssuesForbiddenError(repo.full_name.clone())),
+            _ => e,
+        })?;
+    if query.limit == Some(issues.len()) {
+        log::info!(
+            "Fetched the first {} issues of {}, not paginating further",
+            issues.len(),
+            repo
+        );
+    } else {
+        log::info!("Fetched {} issues of {}", issues.len(), repo);
+    }
+    Ok(issues)
+}
+
+/// Who a token belongs to and what it may do.
+#[derive(Debug, Clone)]
+pub struct TokenInfo {
+    pub login: String,
+    /// The OAuth scopes of a classic token. Fine-grained tokens don't expose their permissions,
+    /// so this is `None` for them.
+    pub scopes: Option<Vec<String>>,
+}
+
+impl TokenInfo {
+    /// Whether the token has `scope`. Unknown scopes count as present.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.iter().any(|s| s == scope))
+    }
+}
+
+/// Looks up the user and scopes of the token. Only the first call per client sends a request.
+pub async fn get_token_info(client: &GitHub) -> Result<&TokenInfo, Box<dyn std::error::Error>> {
+    client
+        .token_info
+        .get_or_try_init(|| fetch_token_info(client))
+        .await
+}
+
+/// Looks up the login of the user the token belongs to, see [`get_token_info`].
+pub async fn get_authenticated_user(client: &GitHub) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(get_token_info(client).await?.login.clone())
+}
+
+async fn fetch_token_info(client: &GitHub) -> Result<TokenInfo, Box<dyn std::error::Error>> {
+    let res = send_with_retry(client, &client.url("/user"), None).await?;
+
+    let status = res.status();
+    if status != StatusCode::OK {
+        let text = res.text().await?;
+        return Err(error_for_status(status, text));
+    }
+
+    // Classic tokens list their scopes, possibly none at all; fine-grained tokens send nothing.
+    let scopes = res
+        .headers()
+        .get("X-OAuth-Scopes")
+        .and_then(|value| value.to_str().ok())
+        .map(|scopes| {
+            scopes
+                .split(',')
+                .map(str::trim)
+                .filter(|scope| !scope.is_empty())
+                .map(str::to_string)
+                .collect()
+        });
+    let login = res.json::<User>().await?.login;
+    Ok(TokenInfo { login, scopes })
+}
+
+/// A rate limit budget as reported by `/rate_limit`.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy)]
+pub struct RateBudget {
+    pub limit: u32,
+    pub remaining: u32,
+    /// When the budget is refilled, in seconds since the epoch.
+    pub reset: i64,
+}
+
+impl RateBudget {
+    pub fn reset_at(&self) -> Timestamp {
+        Timestamp::from_unix(self.reset)
+    }
+}
+
+/// The budgets of the REST API in general and of searches, which have their own.
+#[derive(serde::Deserialize, Debug, Clone, Copy)]
+pub struct RateLimits {
+    pub core: RateBudget,
+    pub search: RateBudget,
+}
+
+/// Looks up the remaining rate limit. The request itself doesn't count against it.
+pub async fn get_rate_limits(client: &GitHub) -> Result<RateLimits, Box<dyn std::error::Error>> {
+    #[derive(serde::Deserialize)]
+    struct Response {
+        resources: RateLimits,
+    }
+
+    let res = send_with_retry(client, &client.url("/rate_limit"), None).await?;
+    let status = res.status();
+    if status != StatusCode::OK {
+        let text = res.text().await?;
+        return Err(error_for_status(status, text));
+    }
+    Ok(res.json::<Response>().await?.resources)
+}
+
+/// Posts `payload` as JSON to the webhook at `url`, failing unless it answers with success.
+pub async fn post_webhook(
+    http: &reqwest::Client,
+    url: &str,
+    payload: &serde_json::Value,
+    timeout: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let res = http.post(url).json(payload).timeout(timeout).send().await?;
+    let status = res.status();
+    if !status.is_success() {
+        let text = res.text().await?;
+        return Err(format!("the webhook answered {}: {}", status, text.trim()).into());
+    }
+    Ok(())
+}
+
+/// Adds `login` to the assignees of `issue`. GitHub silently drops assignees lacking
+/// access to the repository, so the response is checked for the login as well.
+pub async fn assign_issue(
+    client: &GitHub,
+    repo: &Repo,
+    issue: &Issue,
+    login: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let url = client.url(&format!(
+        "/repos/{}/issues/{}/assignees",
+        repo.full_name, issue.number
+    ));
+    let req = client
+        .http
+        .post(url)
+        .json(&serde_json::json!({ "assignees": [login] }));
+    let res = client.send(req).await?;
+
+    match res.status() {
+        StatusCode::CREATED | StatusCode::OK => {
+            if res.json::<Issue>().await?.is_assigned_to(login) {
+                Ok(())
+            } else {
+                Err(format!("{} can't be assigned to issues in {}.", login, repo).into())
+            }
+        }
+        StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => {
+            Err(format!("No write access to {}.", repo).into())
+        }
+        status => {
+            let text = res.text().await?;
+            Err(error_for_status(status, text))
+        }
+    }
+}
+
+/// The search API never returns more results than this for a single query.
+pub const MAX_SEARCH_RESULTS: u32 = 1000;
+
+#[derive(serde::Deserialize, Debug)]
+struct SearchPage {
+    total_count: u32,
+    items: Vec<Issue>,
+}
+
+/// Finds issues matching the search `query`, e.g. `is:open is:issue user:octocat label:bug`.
+/// Returns the issues together with the total number of matches, which may exceed
+/// [`MAX_SEARCH_RESULTS`] and thus the number of issues returned.
+pub async fn search_issues(
+    client: &GitHub,
+    query: &str,
+) -> Result<(Vec<Issue>, u32), Box<dyn std::error::Error>> {
+    let url = reqwest::Url::parse_with_params(
+        &client.url("/search/issues"),
+        [("q", query), ("per_page", "100")],
+    )?;
+
+    let mut total_count = 0;
+    let issues = fetch_pages(client, url.to_string(), None, |page: SearchPage| {
+        total_count = page.total_count;
+        page.items
+    })
+    .await?;
+    Ok((issues, total_count))
+}
+
+/// Fetches the issues of all `repos`, with at most `concurrency` requests in flight.
+/// Every issue is paired with the repository it belongs to, in the order of `repos`.
+/// `on_progress` is called with the number of repositories done whenever one completes.
+/// A repository whose issues take longer than `timeout_per_repo` is skipped with a warning,
+/// so a slow one doesn't hold up the rest.
+/// Repositories with issues disabled, that don't exist or whose issues the token may not read
+/// are skipped with a warning. The last is an error only if it happened to every repository.
+pub async fn get_issues_for_repos<'a>(
+    client: &GitHub,
+    repos: &'a [Repo],
+    query: &IssueQuery,
+    concurrency: usize,
+    timeout_per_repo: Duration,
+    mut on_progress: impl FnMut(usize),
+) -> Result<Vec<(&'a Repo, Issue)>, Box<dyn std::error::Error>> {
+    let mut done = 0;
+    let mut results = stream::iter(repos.iter().enumerate())
+        .map(|(index, repo)| async move {
+            let issues = tokio::time::timeout(timeout_per_repo, get_issues(client, repo, query));
+            (index, repo, issues.await)
+        })
+        .buffer_unordered(concurrency.max(1))
+        .inspect(|_| {
+            done += 1;
+            on_progress(done);
+        })
+        .collect::<Vec<_>>()
+        .await;
+    results.sort_by_key(|(index, _, _)| *index);
+
+    let mut pool = Vec::new();
+    let mut forbidden = None;
+    let mut readable = 0;
+    for (_, repo, issues) in results {
+        let Ok(issues) = issues else {
+            log::warn!(
+                "Fetching the issues of {} took longer than {}s, skipping it.",
+                repo,
+                timeout_per_repo.as_secs_f64()
+            );
+            continue;
+        };
+        let issues = match issues {
+            Err(e) if e.is::<IssuesDisabledError>() => {
+                log::warn!("{}", e);
+                continue;
+            }
+            Err(e) if e.is::<IssuesForbiddenError>() => {
+                log::warn!("{} Skipping it.", e);
+                forbidden = Some(e);
+                continue;
+            }
+            Err(e) if matches!(e.downcast_ref(), Some(BadRequestError(404, _))) => {
+                log::warn!("Repository '{}' not found, skipping it.", repo);
+                continue;
+            }
+            issues => issues.map_err(|e| ContextError::new(repo.to_string(), e))?,
+        };
+        pool.extend(issues.into_iter().map(|issue| (repo, issue)));
+        readable += 1;
+    }
+    // Only give up if no repository at all could be read.
+    match forbidden {
+        Some(e) if readable == 0 => Err(e),
+        _ => Ok(pool),
+    }
+}
+
+/// Reads a token from `path`, ignoring surrounding whitespace.
+pub fn read_token_file(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read token file {}: {}", path.display(), e))?;
+    let token = contents.trim();
+    if token.is_empty() {
+        return Err(format!("Token file {} is empty.", path.display()).into());
+    }
+    Ok(token.to_string())
+}
+
+/// Environment variables consulted for a token when none is passed explicitly, in priority order.
+pub const TOKEN_ENV_VARS: [&str; 3] = ["ISSUE_ROULETTE_TOKEN", "GH_TOKEN", "GITHUB_TOKEN"];
+
+/// Builds the `Authorization` header from `token`, falling back to the first of
+/// [`TOKEN_ENV_VARS`] that is set.
+pub fn get_token(
+    token: Option<String>,
+) -> Result<Option<HeaderValue>, reqwest::header::InvalidHeaderValue> {
+    let token = token.or_else(|| {
+        TOKEN_ENV_VARS
+            .iter()
+            .find_map(|name| std::env::var(name).ok())
+    });
+    if let Some(token) = token {
+        let value = HeaderValue::from_str(&format!("Bearer {}", token))?;
+        Ok(Some(value))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Reply, TestServer};
+    use serde_json::json;
+
+    fn client(server: &TestServer) -> GitHub {
+        let http = build_http_client(&None, &HttpOptions::default()).unwrap();
+        GitHub::new(http).with_base_url(server.url.clone())
+    }
+
+    #[tokio::test]
+    async fn full_last_page_needs_no_extra_request() {
+        let server = TestServer::bind().await;
+        server.serve(|_| {
+            let repos = (0..100)
+                .map(|i| testing::repo_json(&format!("octocat/repo-{}", i)))
+                .collect::<Vec<_>>();
+            Reply::json(json!(repos))
+        });
+        let repos = get_all_repos(&client(&server), &[]).await.unwrap();
+        assert_eq!(repos.len(), 100);
+        assert_eq!(server.paths(), ["/user/repos?per_page=100"]);
+    }
+
+    #[tokio::test]
+    async fn rate_limited_request_is_retried() {
+        let server = TestServer::bind().await;
+        let attempts = std::sync::atomic::AtomicUsize::new(0);
+        server.serve(move |_| {
+            if attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                // Resetting right away, so the retry needn't wait.
+                Reply::status(403, json!({ "message": "API rate limit exceeded" }))
+                    .header("X-RateLimit-Remaining", 0)
+                    .header("X-RateLimit-Reset", Timestamp::now().unix())
+            } else {
+                Reply::json(json!([]))
+            }
+        });
+        let client = client(&server);
+        let res = send_with_retry(&client, &client.url("/user/repos"), None)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(server.paths().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn distant_rate_limit_reset_is_reported() {
+        let server = TestServer::bind().await;
+        server.serve(|_| {
+            Reply::status(403, json!({ "message": "API rate limit exceeded" }))
+                .header("X-RateLimit-Remaining", 0)
+                .header("X-RateLimit-Reset", Timestamp::now().unix() + 3600)
+        });
+        let client = client(&server);
+        let error = send_with_retry(&client, &client.url("/user/repos"), None)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error.downcast_ref(), Some(RateLimitedError(wait)) if *wait > MAX_RATE_LIMIT_WAIT)
+        );
+        assert_eq!(server.paths().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn secondary_rate_limit_is_handled_apart() {
+        let server = TestServer::bind().await;
+        server.serve(|_| {
+            let body = json!({
+                "message": "You have exceeded a secondary rate limit. Please wait a few minutes before you try again.",
+                "documentation_url": "https://docs.github.com/rest/overview/rate-limits-for-the-rest-api",
+            });
+            Reply::status(403, body).header("Retry-After", 120)
+        });
+        let client = client(&server).with_concurrency(4);
+        let error = send_with_retry(&client, &client.url("/repos/octo/repo/issues"), None)
+            .await
+            .unwrap_err();
+        // Too long to wait for, so no retry, but requests take turns from now on.
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SecondaryRateLimitedError(wait)) if *wait == Duration::from_secs(120)
+        ));
+        assert_eq!(server.paths().len(), 1);
+        assert_eq!(client.throttle.set_max(4), 1);
+    }
+
+    #[test]
+    fn secondary_rate_limit_bodies_are_recognized() {
+        assert!(is_secondary_rate_limit(
+            r#"{"message":"You have exceeded a secondary rate limit."}"#
+        ));
+        assert!(is_secondary_rate_limit(
+            r#"{"message":"You have triggered an abuse detection mechanism."}"#
+        ));
+        assert!(!is_secondary_rate_limit(
+            r#"{"message":"API rate limit exceeded for 127.0.0.1."}"#
+        ));
+    }
+
+    #[tokio::test]
+    async fn transient_failure_is_retried() {
+        let server = TestServer::bind().await;
+        let attempts = std::sync::atomic::AtomicUsize::new(0);
+        server.serve(move |_| {
+            if attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                Reply::status(503, json!({ "message": "Service Unavailable" }))
+            } else {
+                Reply::json(json!([testing::repo_json("octocat/a")]))
+            }
+        });
+        let repos = get_public_repos(&client(&server), "octocat").await.unwrap();
+        assert_eq!(repos.len(), 1);
+        assert_eq!(server.paths().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let server = TestServer::bind().await;
+        server.serve(|_| Reply::status(422, json!({ "message": "Validation Failed" })));
+        let client = client(&server);
+        let error = fetch_all_pages::<Repo>(&client, client.url("/user/repos"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(BadRequestError(422, _))
+        ));
+        assert_eq!(server.paths().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn gone_issues_mean_issues_disabled() {
+        let server = TestServer::bind().await;
+        server.serve(|_| {
+            Reply::status(
+                410,
+                json!({ "message": "Issues are disabled for this repo" }),
+            )
+        });
+        let repo = testing::repo("octo/repo");
+        let error = get_issues(&client(&server), &repo, &IssueQuery::default())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error.downcast_ref(), Some(IssuesDisabledError(name)) if name == "octo/repo")
+        );
+    }
+
+    #[tokio::test]
+    async fn disabled_issues_are_not_requested() {
+        let server = TestServer::bind().await;
+        server.serve(|_| Reply::json(json!([])));
+        let repo = Repo {
+            has_issues: false,
+            ..testing::repo("octo/repo")
+        };
+        let error = get_issues(&client(&server), &repo, &IssueQuery::default())
+            .await
+            .unwrap_err();
+        assert!(error.is::<IssuesDisabledError>());
+        assert!(server.paths().is_empty());
+    }
+
+    fn forbidden_sso(request: &test_server::Request) -> Option<Reply> {
+        request.path.starts_with("/repos/octo/sso").then(|| {
+            let body = json!({ "message": "Resource protected by organization SAML enforcement." });
+            Reply::status(403, body)
+        })
+    }
+
+    #[tokio::test]
+    async fn forbidden_repos_are_skipped() {
+        let server = TestServer::bind().await;
+        server.serve(|request| {
+            forbidden_sso(request).unwrap_or_else(|| {
+                Reply::json(json!([testing::issue_json(
+                    "octo/open",
+                    1,
+                    "Readable",
+                    &[]
+                )]))
+            })
+        });
+        let repos = [testing::repo("octo/sso"), testing::repo("octo/open")];
+        let pool = get_issues_for_repos(
+            &client(&server),
+            &repos,
+            &IssueQuery::default(),
+            2,
+            Duration::from_secs(10),
+            |_| {},
+        )
+        .await
+        .unwrap();
+        let names = pool
+            .iter()
+            .map(|(repo, issue)| format!("{}#{}", repo, issue.number))
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["octo/open#1"]);
+    }
+
+    #[tokio::test]
+    async fn all_forbidden_repos_are_an_error() {
+        let server = TestServer::bind().await;
+        server.serve(|request| forbidden_sso(request).unwrap());
+        let repos = [testing::repo("octo/sso-a"), testing::repo("octo/sso-b")];
+        let error = get_issues_for_repos(
+            &client(&server),
+            &repos,
+            &IssueQuery::default(),
+            2,
+            Duration::from_secs(10),
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert!(error.is::<IssuesForbiddenError>());
+    }
+
+    #[tokio::test]
+    async fn cyclic_next_link_terminates() {
+        let server = TestServer::bind().await;
+        let first = format!("{}/user/repos?per_page=100", server.url);
+        let second = format!("{}&page=2", first);
+        let (to_second, to_first) = (second.clone(), first.clone());
+        server.serve(move |request| {
+            // The second page links back to the first.
+            let (repo, next) = if request.path.ends_with("&page=2") {
+                ("octocat/b", &to_first)
+            } else {
+                ("octocat/a", &to_second)
+            };
+            Reply::json(json!([testing::repo_json(repo)]))
+                .header("Link", format!("<{}>; rel=\"next\"", next))
+        });
+        let repos = fetch_all_pages::<Repo>(&client(&server), first)
+            .await
+            .unwrap();
+        assert_eq!(repos.len(), 2);
+        assert_eq!(server.paths().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn endless_pagination_stops_at_max_pages() {
+        let server = TestServer::bind().await;
+        let url = server.url.clone();
+        server.serve(move |request| {
+            let page = request
+                .path
+                .rsplit('=')
+                .next()
+                .unwrap()
+                .parse::<u32>()
+                .unwrap_or(1);
+            let next = format!("<{}/user/repos?page={}>; rel=\"next\"", url, page + 1);
+            Reply::json(json!([testing::repo_json("octocat/a")])).header("Link", next)
+        });
+        let client = client(&server).with_max_pages(3);
+        let repos = fetch_all_pages::<Repo>(&client, client.url("/user/repos?page=1"))
+            .await
+            .unwrap();
+        assert_eq!(repos.len(), 3);
+        assert_eq!(server.paths().len(), 3);
+    }
+
+    /// Held by tests that set or clear the token variables, which the whole process shares.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Clears every variable in [`TOKEN_ENV_VARS`], returning the lock that keeps other tests
+    /// from touching them meanwhile.
+    fn clear_token_env() -> std::sync::MutexGuard<'static, ()> {
+        let guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        for name in TOKEN_ENV_VARS {
+            std::env::remove_var(name);
+        }
+        guard
+    }
+
+    #[test]
+    fn get_token_uses_explicit_token() {
+        let _env = clear_token_env();
+        std::env::set_var("GITHUB_TOKEN", "from-env");
+        let header = get_token(Some("explicit".to_string())).unwrap().unwrap();
+        assert_eq!(header, "Bearer explicit");
+        std::env::remove_var("GITHUB_TOKEN");
+    }
+
+    #[test]
+    fn get_token_falls_back_to_env() {
+        let _env = clear_token_env();
+        assert_eq!(get_token(None).unwrap(), None);
+        std::env::set_var("ISSUE_ROULETTE_TOKEN", "from-env");
+        assert_eq!(get_token(None).unwrap().unwrap(), "Bearer from-env");
+        std::env::remove_var("ISSUE_ROULETTE_TOKEN");
+        assert_eq!(get_token(None).unwrap(), None);
+    }
+
+    #[test]
+    fn get_token_falls_back_in_priority_order() {
+        let _env = clear_token_env();
+        // Set from the lowest priority up, each new one taking over.
+        for name in TOKEN_ENV_VARS.iter().rev() {
+            std::env::set_var(name, format!("token-of-{}", name));
+            let expected = format!("Bearer token-of-{}", name);
+            assert_eq!(get_token(None).unwrap().unwrap(), expected.as_str());
+        }
+        // Removed from the highest priority down, each next one taking over.
+        for (index, name) in TOKEN_ENV_VARS.iter().enumerate() {
+            std::env::remove_var(name);
+            let header = get_token(None).unwrap();
+            match TOKEN_ENV_VARS.get(index + 1) {
+                Some(next) => {
+                    assert_eq!(
+                        header.unwrap(),
+                        format!("Bearer token-of-{}", next).as_str()
+                    )
+                }
+                None => assert_eq!(header, None),
+            }
+        }
+    }
+
+    #[test]
+    fn get_token_rejects_newlines() {
+        let _env = clear_token_env();
+        // A newline would split the header in two.
+        let result = get_token(Some("ghp_abc\nX-Injected: 1".to_string()));
+        let _: reqwest::header::InvalidHeaderValue = result.unwrap_err();
+    }
+
+    #[test]
+    fn sanitize_escapes_bidi_overrides() {
+        assert_eq!(
+            sanitize("\u{202E}gnp.exe"),
+            "\\u{202e}gnp.exe",
+            "a right-to-left override must not reach the terminal"
+        );
+        assert_eq!(
+            sanitize("a\u{2066}b\u{2069}c\u{200f}"),
+            "a\\u{2066}b\\u{2069}c\\u{200f}"
+        );
+    }
+
+    #[test]
+    fn sanitize_replaces_line_breaks_and_escapes_controls() {
+        assert_eq!(sanitize("one\ntwo\r\tthree"), "one two  three");
+        assert_eq!(sanitize("\u{1b}[2Jcleared"), "\\u{1b}[2Jcleared");
+        assert_eq!(sanitize("plain ünïcode"), "plain ünïcode");
+    }
+
+    #[test]
+    fn issue_display_is_sanitized() {
+        let issue = testing::issue("octo/repo", 1, "evil\u{202e}title");
+        assert_eq!(
+            issue.to_string(),
+            "[1] evil\\u{202e}title -> https://github.com/octo/repo/issues/1"
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! A small `log` backend writing to stderr, configured like `env_logger` through `RUST_LOG`.
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Per-target maximum levels. A directive without a target applies to everything else.
+struct Logger {
+    directives: Vec<(Option<String>, LevelFilter)>,
+}
+
+impl Logger {
+    fn level_for(&self, target: &str) -> LevelFilter {
+        // The longest matching target prefix wins, like in `env_logger`.
+        self.directives
+            .iter()
+            .filter(|(prefix, _)| {
+                prefix
+                    .as_ref()
+                    .is_none_or(|p| target.starts_with(p.as_str()))
+            })
+            .max_by_key(|(prefix, _)| prefix.as_ref().map_or(0, String::len))
+            .map_or(LevelFilter::Off, |(_, level)| *level)
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Parses `RUST_LOG` style directives such as `debug` or `warn,issue_roulette=trace`.
+/// Directives that don't parse are skipped.
+pub fn parse_log_directives(spec: &str) -> Vec<(Option<String>, LevelFilter)> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .filter_map(|directive| match directive.split_once('=') {
+            Some((target, level)) => Some((Some(target.to_string()), level.parse().ok()?)),
+            None => Some((None, directive.parse().ok()?)),
+        })
+        .collect()
+}
+
+/// Installs the logger with `directives`. Does nothing if a logger is already installed.
+pub fn init_logger(directives: Vec<(Option<String>, LevelFilter)>) {
+    let max_level = directives
+        .iter()
+        .map(|(_, level)| *level)
+        .max()
+        .unwrap_or(LevelFilter::Off);
+    if log::set_boxed_logger(Box::new(Logger { directives })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
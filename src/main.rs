@@ -1,165 +1,2796 @@
-use clap::Parser;
-use rand::seq::SliceRandom;
-use reqwest::{
-    header::{HeaderMap, HeaderValue},
-    StatusCode,
+use anstyle::{AnsiColor, RgbColor, Style};
+use clap::{
+    parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
 };
+use issue_roulette::{
+    assign_issue, build_http_client, create_installation_token, get_all_repos,
+    get_authenticated_user, get_installation_repos, get_issues, get_issues_for_repos,
+    get_org_repos, get_public_repos, get_rate_limits, get_repo, get_repos_with_issues,
+    get_starred_repos, get_token, get_token_info, glob_match, init_logger, parse_base_url,
+    parse_duration, parse_log_directives, pick, post_webhook, read_token_file, sanitize,
+    search_issues, Affiliation, AppCredentials, BadCredentialsError, BadRequestError, Config,
+    ContextError, GitHub, History, HttpOptions, Issue, IssueQuery, IssueState, IssuesDisabledError,
+    IssuesForbiddenError, LastRuns, Pick, RateLimitedError, Recording, Repo, RepoFilterExpr,
+    RepoListCache, ResponseCache, SecondaryRateLimitedError, Spinner, Template, TimeoutError,
+    Timestamp, TokenInfo, DEFAULT_BASE_URL, DEFAULT_CONCURRENCY, DEFAULT_MAX_PAGES,
+    DEFAULT_MAX_RETRIES, DEFAULT_USER_AGENT, MAX_GRAPHQL_ISSUES_PER_REPO, MAX_SEARCH_RESULTS,
+    TOKEN_ENV_VARS,
+};
+use log::LevelFilter;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use reqwest::header::HeaderValue;
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode, Stdio};
+use std::time::Duration;
+
+#[cfg(test)]
+mod fixtures;
 
 /// Simple program to choose a random open issue to work on.
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_help = EXIT_CODES_HELP)]
 struct Args {
-    /// The handle for the GitHub user account.
+    /// Roll across the public repositories of this GitHub user. Without it, a token rolls across
+    /// your own repositories, private ones included. Required unless a token, --org or --repo is given.
     #[arg(short, long)]
-    username: String,
+    username: Option<String>,
+
+    /// Roll across the repositories of a GitHub organization. With a token this includes private repositories it can see.
+    #[arg(long, conflicts_with = "username")]
+    org: Option<String>,
+
+    /// Roll within a single repository, given as owner/name, instead of listing repositories.
+    #[arg(long, value_name = "OWNER/NAME", conflicts_with_all = ["username", "org"])]
+    repo: Option<String>,
+
+    /// Roll only on the repositories listed in this file, one owner/name per line, instead of
+    /// listing repositories. Blank lines and lines starting with # are ignored. The issues of all
+    /// of them are fetched; listed repositories that don't exist are skipped with a warning.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["username", "org", "repo", "starred", "graphql", "search"]
+    )]
+    repos_file: Option<PathBuf>,
+
+    /// With a token, list the repositories you are related to in any of these ways. Comma separated.
+    /// Forks you collaborate on are still skipped unless --include-forked-repos is given.
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [Affiliation::Owner, Affiliation::Collaborator, Affiliation::OrganizationMember]
+    )]
+    affiliation: Vec<Affiliation>,
+
+    /// Roll across starred repositories instead of owned ones: those starred by --username, or by you when only a token is given.
+    #[arg(long, conflicts_with_all = ["org", "repo", "search"])]
+    starred: bool,
+
+    /// Fetch your repositories and their newest 50 issues each, or --issues-per-repo up to 100,
+    /// through the GraphQL API, which takes one request per 100 repositories instead of one per
+    /// repository. Requires a token.
+    #[arg(long, conflicts_with_all = ["username", "org", "repo", "starred", "search"])]
+    graphql: bool,
 
     /// Include forked repositories. Defaults to false.
-    #[arg(long)]
+    #[arg(long, conflicts_with = "forks_only")]
     include_forked_repos: bool,
 
-    /// Authorization token to include private repositories. Can also be supplied via ENV: ISSUE_ROULETTE_TOKEN
+    /// Only consider forked repositories, e.g. to work on issues filed against your forks.
+    #[arg(long)]
+    forks_only: bool,
+
+    /// Include archived repositories. Defaults to false.
+    #[arg(long)]
+    include_archived: bool,
+
+    /// Only consider repositories with at least this many open issues.
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    min_issues: u32,
+
+    /// Only consider repositories with at least this many stars. Private repositories rarely
+    /// have many, so this tends to leave them out.
+    #[arg(long, value_name = "N", conflicts_with = "search")]
+    min_stars: Option<u32>,
+
+    /// Only consider private repositories. Requires a token.
+    #[arg(long, conflicts_with = "public_only")]
+    private_only: bool,
+
+    /// Only consider public repositories.
+    #[arg(long)]
+    public_only: bool,
+
+    /// Only consider repositories whose primary language is this, ignoring case. Repositories without a detected language are skipped.
+    #[arg(long, value_name = "NAME")]
+    language: Option<String>,
+
+    /// Only consider repositories tagged with this topic. Repeat to require several topics.
+    #[arg(long = "topic", value_name = "NAME")]
+    topics: Vec<String>,
+
+    /// Only consider repositories whose owner/name matches this glob, e.g. "me/*-rs". Repeatable.
+    #[arg(long, value_name = "GLOB")]
+    include_repo: Vec<String>,
+
+    /// Skip repositories whose owner/name matches this glob, e.g. "*/legacy-*". Repeatable, wins over --include-repo.
+    #[arg(long, value_name = "GLOB")]
+    exclude_repo: Vec<String>,
+
+    /// Only consider repositories matching this expression, on top of the other filters,
+    /// e.g. "archived=false && stars>=10 && language=Rust". Fields are name (a wildcard
+    /// pattern), fork, archived, private, has_issues, open_issues, stars, language and topic,
+    /// compared with = and !=, numbers also with <, <=, > and >=, and combined with &&, ||, !
+    /// and parentheses. Forks still need --include-forked-repos.
+    #[arg(long, value_name = "EXPR")]
+    repo_filter: Option<RepoFilterExpr>,
+
+    /// Read defaults for these flags from this TOML file instead of ~/.config/issue-roulette/config.toml.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Authorization token to include private repositories. Can also be supplied via ENV: ISSUE_ROULETTE_TOKEN, GH_TOKEN or GITHUB_TOKEN, checked in that order
     #[arg(short, long)]
     token: Option<String>,
+
+    /// Read the authorization token from this file instead of passing it on the command line. --token takes precedence.
+    #[arg(long, value_name = "PATH")]
+    token_file: Option<PathBuf>,
+
+    /// Authenticate as an installation of the GitHub App with this ID instead of with a token,
+    /// e.g. in CI. Tokens are minted from --private-key-file for --installation-id.
+    /// Without --username, --org or --repo, rolls on the repositories of the installation.
+    #[arg(long, value_name = "ID", requires_all = ["private_key_file", "installation_id"], conflicts_with_all = ["token", "token_file"])]
+    app_id: Option<u64>,
+
+    /// The private key of the --app-id app, a PEM file as downloaded from its settings.
+    #[arg(long, value_name = "PATH", requires = "app_id")]
+    private_key_file: Option<PathBuf>,
+
+    /// The installation of the --app-id app to act as.
+    #[arg(long, value_name = "ID", requires = "app_id")]
+    installation_id: Option<u64>,
+
+    /// Seconds a single request may take before it is abandoned.
+    #[arg(long, value_name = "SECONDS", default_value = "30", value_parser = parse_seconds)]
+    timeout: Duration,
+
+    /// Seconds fetching all issues of one repository may take when fetching those of many,
+    /// after which the repository is skipped.
+    #[arg(long, value_name = "SECONDS", default_value = "10", value_parser = parse_seconds)]
+    timeout_per_repo: Duration,
+
+    /// Seconds establishing a connection may take.
+    #[arg(long, value_name = "SECONDS", default_value = "10", value_parser = parse_seconds)]
+    connect_timeout: Duration,
+
+    /// How often to retry a request failing with a 502, 503 or 504 or a dropped connection,
+    /// waiting longer after each attempt.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_RETRIES)]
+    max_retries: u32,
+
+    /// Follow at most this many pages of any listing, in case a server paginates endlessly.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_PAGES, value_parser = clap::value_parser!(u32).range(1..))]
+    max_pages: u32,
+
+    /// Send all requests through this proxy, e.g. http://proxy:3128. HTTPS_PROXY, HTTP_PROXY and NO_PROXY are honored otherwise.
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// User-Agent header sent with every request, for proxies or rate-limit rules keyed on it.
+    #[arg(long, value_name = "AGENT", default_value = DEFAULT_USER_AGENT)]
+    user_agent: String,
+
+    /// Root of the GitHub API. Use https://<host>/api/v3 for GitHub Enterprise Server.
+    #[arg(long, value_name = "URL", default_value = DEFAULT_BASE_URL, value_parser = parse_base_url)]
+    base_url: String,
+
+    /// Which issues to consider by state.
+    #[arg(long, value_enum, default_value_t = IssueState::Open)]
+    state: IssueState,
+
+    /// Include pull requests alongside issues. Defaults to false.
+    #[arg(long)]
+    include_prs: bool,
+
+    /// Skip draft pull requests, which are rarely ready for anyone to pick up. Only matters with
+    /// --include-prs.
+    #[arg(long)]
+    exclude_drafts: bool,
+
+    /// Only consider issues carrying this label. Repeat to require several labels.
+    #[arg(short, long = "label", value_name = "NAME")]
+    labels: Vec<String>,
+
+    /// Skip issues carrying this label, even if they match --label. Repeat to skip several.
+    #[arg(long = "exclude-label", value_name = "NAME")]
+    exclude_labels: Vec<String>,
+
+    /// Favor issues carrying this label without skipping the others: their weight is
+    /// multiplied by --prefer-weight, on top of --bias. Repeat to favor several labels.
+    #[arg(long = "prefer-label", value_name = "NAME")]
+    prefer_labels: Vec<String>,
+
+    /// How many times as likely issues with a --prefer-label are chosen.
+    #[arg(long, value_name = "FACTOR", default_value_t = 3.0, value_parser = parse_positive)]
+    prefer_weight: f64,
+
+    /// Only consider onboarding issues, i.e. those labeled "good first issue", "good-first-issue" or "help wanted".
+    #[arg(long)]
+    good_first_issue: bool,
+
+    /// Seed for the random selection. The same seed and candidates always yield the same issue.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// How to print the chosen issue. Progress messages always go to stderr. With json, errors
+    /// are printed to stderr as {"error": KIND, "message": TEXT}, where KIND is one of
+    /// bad_credentials, rate_limited and secondary_rate_limited (both with retry_after in
+    /// seconds), timeout, http (with the response status) or other.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+    format: OutputFormat,
+
+    /// Print each issue with this template instead, e.g. "{repo}#{number}: {title}". Placeholders
+    /// are {number}, {title}, {url}, {repo} and {labels}; write {{ and }} for literal braces.
+    #[arg(long, value_name = "TEMPLATE", conflicts_with = "format")]
+    template: Option<Template>,
+
+    /// Number of distinct issues to choose. One issue is printed per line.
+    #[arg(short = 'n', long, default_value_t = 1)]
+    count: usize,
+
+    /// Never choose two issues with nearly the same title, such as a tracking issue filed in
+    /// several repositories.
+    #[arg(long)]
+    dedup: bool,
+
+    /// Don't color the output. Color is also off when stdout isn't a terminal or NO_COLOR is set.
+    #[arg(long)]
+    no_color: bool,
+
+    /// Frame the chosen issue with plain `>>> <<<` instead of emoji, for terminals and logs
+    /// without emoji support. Also the case when stdout isn't a terminal.
+    #[arg(long)]
+    no_emoji: bool,
+
+    /// Also append the chosen issues to this file, one per line in the --format, e.g. JSON Lines.
+    #[arg(long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// POST every chosen issue to this webhook, e.g. of a Slack or Discord channel. A webhook
+    /// that fails is reported with a warning, the issue is printed anyway.
+    #[arg(long, value_name = "URL")]
+    notify_url: Option<String>,
+
+    /// Shape of the --notify-url payload: raw posts {"number", "title", "url", "repository"},
+    /// slack and discord a message those webhooks accept.
+    #[arg(long, value_enum, default_value_t = NotifyFormat::Raw)]
+    notify_format: NotifyFormat,
+
+    /// Choose up to this many issues from each of --count repositories, instead of --count issues overall.
+    #[arg(long, value_name = "N", conflicts_with_all = ["search", "weighted"])]
+    count_per_repo: Option<usize>,
+
+    /// Show log messages up to this level on stderr. Defaults to RUST_LOG, or warn if that is unset.
+    #[arg(long, value_enum)]
+    log_level: Option<LogLevel>,
+
+    /// Log every API response and the remaining rate limit to stderr.
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Print nothing but the result and errors to stderr, no progress, notes or spinner.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// How long a listing of repositories is reused before it is fetched again, e.g. 30m or 1d.
+    #[arg(long, value_name = "DURATION", default_value = "1h", value_parser = parse_duration_arg)]
+    cache_ttl: Duration,
+
+    /// Fetch the repository listing again even if a recent one is cached.
+    #[arg(long)]
+    refresh: bool,
+
+    /// Don't use or update the on-disk caches of GitHub responses and repository listings.
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Save every API response of this run to a file, for --replay to answer the same run from
+    /// later without a network. Implies --no-cache.
+    #[arg(long, value_name = "PATH", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Answer every API request from a file saved with --record instead of asking GitHub. The
+    /// other options must match the recorded run. Implies --no-cache.
+    #[arg(long, value_name = "PATH")]
+    replay: Option<PathBuf>,
+
+    /// Fetch the issues of every candidate repository and draw from all of them at once, instead
+    /// of picking a repository first. Every issue is then equally likely, but this takes at least
+    /// one request per repository instead of one in total; bound it with --max-repos.
+    #[arg(long)]
+    all_issues: bool,
+
+    /// Draw from a random sample of at most this many of the filtered repositories. Bounds the
+    /// requests made for large accounts, at the price of never seeing the repositories left out.
+    #[arg(long, value_name = "N", conflicts_with = "search")]
+    max_repos: Option<usize>,
+
+    /// Fetch at most this many issues of each repository, the most recently created ones,
+    /// bounding the requests made for repositories with thousands of issues. Slightly biases
+    /// against the oldest issues of large repositories, which are never drawn; --weighted still
+    /// picks repositories by their total number of open issues.
+    #[arg(long, value_name = "N", conflicts_with = "search", value_parser = clap::value_parser!(u32).range(1..))]
+    issues_per_repo: Option<u32>,
+
+    /// Maximum number of requests in flight at the same time, such as for the issues of many
+    /// repositories. Fewer are sent at once as the rate limit runs low.
+    #[arg(long, default_value_t = DEFAULT_CONCURRENCY)]
+    concurrency: usize,
+
+    /// List every candidate issue that survives the filters instead of choosing one.
+    #[arg(long, visible_alias = "dry-run")]
+    list: bool,
+
+    /// Print the listed or chosen issues in this order. Which issues are chosen stays random.
+    #[arg(long, value_enum, value_name = "KEY")]
+    sort: Option<SortKey>,
+
+    /// List the candidate issues in random order, reproducible with --seed, for browsing.
+    /// Together with --count, only that many are listed.
+    #[arg(long, requires = "list", conflicts_with = "sort")]
+    shuffle: bool,
+
+    /// Direction of --sort.
+    #[arg(long, value_enum, default_value_t = SortOrder::Asc, requires = "sort")]
+    order: SortOrder,
+
+    /// Don't skip recently chosen issues and don't remember this pick.
+    #[arg(long)]
+    no_history: bool,
+
+    /// Clear the history of chosen issues for this user, organization or repository first.
+    #[arg(long, conflicts_with = "no_history")]
+    forget: bool,
+
+    /// Number of days a chosen issue is kept out of the pool.
+    #[arg(long, value_name = "DAYS", default_value_t = 7)]
+    history_days: u64,
+
+    /// Assign the chosen issue to yourself. Requires a token with write access to the repository.
+    #[arg(long)]
+    assign_me: bool,
+
+    /// Open the chosen issue in the default browser.
+    #[arg(long, overrides_with = "no_open")]
+    open: bool,
+
+    /// Don't open the chosen issue in a browser, even if enabled elsewhere.
+    #[arg(long, overrides_with = "open")]
+    no_open: bool,
+
+    /// Query the search API for matching issues instead of listing repositories and their issues.
+    /// Much lighter on the rate limit for targeted filters, but only sees the first 1000 matches.
+    #[arg(long, conflicts_with_all = ["all_issues", "weighted"])]
+    search: bool,
+
+    /// Pick the repository with probability proportional to its open issues. By default every
+    /// repository is equally likely, so issues in small repositories are favored; weighting makes
+    /// every open issue roughly equally likely without fetching all of them. Only with --state
+    /// open, as GitHub counts no other issues per repository.
+    #[arg(long, conflicts_with = "all_issues")]
+    weighted: bool,
+
+    /// Favor some issues over others when choosing. oldest and stalest make an issue's chance
+    /// proportional to the days since it was created or last updated, plus one, so an issue
+    /// untouched for a year is about 365 times as likely as one from today. reactions makes it
+    /// proportional to the reactions to the issue, plus one. composite combines both as
+    /// 1 + reactions × --reaction-weight + days since the last update / --stale-days.
+    #[arg(long, value_enum, default_value_t = Bias::None)]
+    bias: Bias,
+
+    /// Explain on stderr how the issues were chosen: from how many candidates, and with what
+    /// chance under --weighted and --bias.
+    #[arg(long, conflicts_with = "list")]
+    explain: bool,
+
+    /// Choose the issue by hand from the candidates, ordered by --sort, instead of at random.
+    /// Falls back to a random pick when not run in a terminal.
+    #[arg(long, conflicts_with_all = ["list", "count", "count_per_repo"])]
+    interactive: bool,
+
+    /// How much each reaction adds to an issue's weight with --bias composite.
+    #[arg(long, value_name = "WEIGHT", default_value_t = 1.0, value_parser = parse_non_negative)]
+    reaction_weight: f64,
+
+    /// Days without an update that add as much weight as one reaction with --bias composite.
+    #[arg(long, value_name = "DAYS", default_value_t = 30.0, value_parser = parse_positive)]
+    stale_days: f64,
+
+    /// Only consider issues last updated before this RFC 3339 timestamp or duration ago, e.g. 30d.
+    #[arg(long, value_name = "WHEN")]
+    updated_before: Option<Timestamp>,
+
+    /// Only consider issues created or updated since the previous --new-only run on the same
+    /// repositories, everything counts as new the first time. Turns a daily run into a feed of
+    /// new issues.
+    #[arg(long)]
+    new_only: bool,
+
+    /// Only consider issues last updated after this RFC 3339 timestamp or duration ago, e.g. 7d.
+    #[arg(long, value_name = "WHEN")]
+    updated_after: Option<Timestamp>,
+
+    /// Only consider issues opened by this GitHub login, or by the token's user for @me.
+    #[arg(long, value_name = "LOGIN")]
+    issue_author: Option<String>,
+
+    /// Only consider issues in the milestone with this title or number. Milestones belong to a
+    /// single repository, so this is mostly useful together with --repo.
+    #[arg(long, value_name = "TITLE|NUMBER", conflicts_with = "no_milestone")]
+    milestone: Option<String>,
+
+    /// Only consider issues without a milestone.
+    #[arg(long)]
+    no_milestone: bool,
+
+    /// Only consider issues with at least this many comments.
+    #[arg(long, value_name = "N")]
+    min_comments: Option<u32>,
+
+    /// Only consider issues with at most this many comments. 0 finds untouched issues.
+    #[arg(long, value_name = "N")]
+    max_comments: Option<u32>,
+
+    /// Only consider issues with at least this many reactions, a measure of community demand.
+    /// Reaction counts come with every issue, which makes responses somewhat larger; GitHub
+    /// Enterprise Server versions that leave them out count as no reactions.
+    #[arg(long, value_name = "N")]
+    min_reactions: Option<u32>,
+
+    /// Include issues that are already assigned to someone. Defaults to false.
+    #[arg(long)]
+    include_assigned: bool,
+
+    /// Only consider issues assigned to this GitHub login, or to the token's user for @me.
+    #[arg(long, value_name = "LOGIN")]
+    assigned_to: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Diagnostic>,
+
+    /// Whether --count was given, on the command line or in the config, rather than defaulted.
+    #[arg(skip)]
+    count_given: bool,
 }
 
-#[derive(serde::Deserialize, Debug)]
-struct Repo {
-    full_name: String,
-    fork: bool,
-    has_issues: bool,
-    open_issues: u32,
+/// Diagnostics instead of the roulette, which runs without a subcommand.
+#[derive(Subcommand, Debug)]
+enum Diagnostic {
+    /// Inspect the token.
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommand,
+    },
+    /// Show the rate limit left for requests and for searches, then exit.
+    Limit,
 }
 
-impl std::fmt::Display for Repo {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.full_name)
+#[derive(Subcommand, Debug)]
+enum AuthCommand {
+    /// Check that the token works and print its user, scopes and rate limit, then exit.
+    Check,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Human,
+    Json,
+    /// A task list line like `- [ ] [owner/repo#123 Title](url)`, for notes and comments.
+    Markdown,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum NotifyFormat {
+    Raw,
+    Slack,
+    Discord,
+}
+
+/// The --notify-url payload announcing `issue`.
+fn notification(format: NotifyFormat, repo: &Repo, issue: &Issue) -> serde_json::Value {
+    match format {
+        NotifyFormat::Raw => serde_json::json!(Selection::new(repo, issue)),
+        NotifyFormat::Slack => {
+            // Slack takes these three as markup, the rest of the text verbatim.
+            let escape = |text: &str| {
+                text.replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;")
+            };
+            let text = format!(
+                "<{}|{}#{}> {}",
+                issue.html_url,
+                repo,
+                issue.number,
+                escape(&sanitize(&issue.title))
+            );
+            serde_json::json!({ "text": text })
+        }
+        NotifyFormat::Discord => {
+            let content = format!(
+                "{}#{} {}\n{}",
+                repo,
+                issue.number,
+                sanitize(&issue.title),
+                issue.html_url
+            );
+            // Titles mentioning @everyone mustn't ping the whole server.
+            serde_json::json!({ "content": content, "allowed_mentions": { "parse": [] } })
+        }
+    }
+}
+
+/// The machine-readable form of `error` for --format json. The `error` kind of the first
+/// error in the source chain that has one is stable, the message is not.
+fn error_json(error: &(dyn std::error::Error + 'static)) -> serde_json::Value {
+    let mut json = std::iter::successors(Some(error), |e| e.source())
+        .find_map(|e| {
+            if e.is::<BadCredentialsError>() {
+                Some(serde_json::json!({ "error": "bad_credentials" }))
+            } else if let Some(RateLimitedError(wait)) = e.downcast_ref() {
+                Some(serde_json::json!({ "error": "rate_limited", "retry_after": wait.as_secs() }))
+            } else if let Some(SecondaryRateLimitedError(wait)) = e.downcast_ref() {
+                Some(serde_json::json!({
+                    "error": "secondary_rate_limited",
+                    "retry_after": wait.as_secs(),
+                }))
+            } else if e.is::<TimeoutError>() {
+                Some(serde_json::json!({ "error": "timeout" }))
+            } else if let Some(BadRequestError(status, _)) = e.downcast_ref() {
+                Some(serde_json::json!({ "error": "http", "status": status }))
+            } else {
+                None
+            }
+        })
+        .unwrap_or_else(|| serde_json::json!({ "error": "other" }));
+    json["message"] = error.to_string().into();
+    json
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SortKey {
+    Created,
+    Updated,
+    Comments,
+    Number,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Bias {
+    /// Every candidate issue is equally likely.
+    None,
+    /// Weight by the time since the issue was created.
+    Oldest,
+    /// Weight by the time since the issue was last updated.
+    Stalest,
+    /// Weight by the number of reactions to the issue.
+    Reactions,
+    /// Weight by both reactions and the time since the last update.
+    Composite,
+}
+
+impl Bias {
+    /// The relative chance of `issue` being chosen, at least 1.
+    fn weight(self, issue: &Issue, now: Timestamp, args: &Args) -> f64 {
+        let days_since = |since: Option<Timestamp>| {
+            let age = since.map_or(Duration::ZERO, |since| since.age(now));
+            age.as_secs_f64() / (24 * 60 * 60) as f64
+        };
+        let reactions = f64::from(issue.reactions.total_count);
+        match self {
+            Bias::None => 1.0,
+            Bias::Oldest => days_since(issue.created_at) + 1.0,
+            Bias::Stalest => days_since(issue.updated_at) + 1.0,
+            Bias::Reactions => reactions + 1.0,
+            Bias::Composite => {
+                1.0 + reactions * args.reaction_weight
+                    + days_since(issue.updated_at) / args.stale_days
+            }
+        }
+    }
+}
+
+/// Whether issues are drawn with weights rather than uniformly.
+fn is_weighted(args: &Args) -> bool {
+    args.bias != Bias::None || !args.prefer_labels.is_empty()
+}
+
+/// The relative chance of `issue` being chosen under --bias and --prefer-label.
+fn issue_weight(issue: &Issue, now: Timestamp, args: &Args) -> f64 {
+    let preferred = args
+        .prefer_labels
+        .iter()
+        .any(|label| issue.has_label(label));
+    let factor = if preferred { args.prefer_weight } else { 1.0 };
+    args.bias.weight(issue, now, args) * factor
+}
+
+/// Which repositories to consider depending on whether they are forks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ForkFilter {
+    Exclude,
+    Include,
+    Only,
+}
+
+impl ForkFilter {
+    fn from_args(args: &Args) -> Self {
+        match (args.include_forked_repos, args.forks_only) {
+            (_, true) => ForkFilter::Only,
+            (true, false) => ForkFilter::Include,
+            (false, false) => ForkFilter::Exclude,
+        }
+    }
+
+    fn allows(self, repo: &Repo) -> bool {
+        match self {
+            ForkFilter::Exclude => !repo.fork,
+            ForkFilter::Include => true,
+            ForkFilter::Only => repo.fork,
+        }
     }
 }
 
-#[derive(serde::Deserialize, Debug)]
-struct Issue {
-    title: String,
+/// Exit code when no repository or issue survived the filters.
+const EXIT_NOTHING_FOUND: u8 = 2;
+/// Exit code when GitHub rejected the token.
+const EXIT_BAD_CREDENTIALS: u8 = 3;
+/// Exit code when the rate limit ran out.
+const EXIT_RATE_LIMITED: u8 = 4;
+
+const EXIT_CODES_HELP: &str = "Exit codes: 0 when an issue was printed, 2 when no repository \
+or issue matched the filters, 3 when the token was rejected, 4 when rate limited, 1 on other errors.";
+
+/// Label names that mark an issue as suitable for newcomers.
+const GOOD_FIRST_ISSUE_LABELS: [&str; 3] = ["good first issue", "good-first-issue", "help wanted"];
+
+/// Fetching the issues of more repositories than this suggests --max-repos.
+const MANY_REPOS: usize = 100;
+
+/// Number of repositories tried in turn when the chosen one has no viable issue.
+const MAX_REPO_ATTEMPTS: usize = 3;
+
+/// The chosen issue together with the repository it was drawn from.
+#[derive(serde::Serialize, Debug)]
+struct Selection<'a> {
     number: u32,
-    html_url: String,
+    title: &'a str,
+    url: &'a str,
+    repository: &'a str,
 }
 
-impl std::fmt::Display for Issue {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[{}] {} -> {}", self.number, self.title, self.html_url)
+impl<'a> Selection<'a> {
+    fn new(repo: &'a Repo, issue: &'a Issue) -> Self {
+        Selection {
+            number: issue.number,
+            title: &issue.title,
+            url: &issue.html_url,
+            repository: &repo.full_name,
+        }
     }
 }
 
+/// Prints a progress note to stderr, unless --quiet was given.
+macro_rules! note {
+    ($args:expr, $($arg:tt)*) => {
+        if !$args.quiet {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
 #[tokio::main]
-async fn main() {
-    let args = Args::parse();
+async fn main() -> ExitCode {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let result = match configure(&mut args, &matches) {
+        Ok(()) => {
+            let format = args.format;
+            run(args).await.map_err(|e| (e, format))
+        }
+        Err(e) => Err((e, args.format)),
+    };
+    match result {
+        Ok(code) => code,
+        Err((e, OutputFormat::Json)) => {
+            eprintln!("{}", error_json(e.as_ref()));
+            exit_code_for(e.as_ref())
+        }
+        Err((e, _)) => {
+            eprintln!("Error: {}", e);
+            exit_code_for(e.as_ref())
+        }
+    }
+}
 
-    let token = get_token(args.token).expect("Failed to build Auth token header.");
-    let client = build_http_client(&token).expect("Failed to build http client.");
+/// Picks the exit code for `error` by the first error in its source chain that has one.
+fn exit_code_for(error: &(dyn std::error::Error + 'static)) -> ExitCode {
+    let code = std::iter::successors(Some(error), |e| e.source()).find_map(|e| {
+        if e.is::<BadCredentialsError>() {
+            Some(EXIT_BAD_CREDENTIALS)
+        } else if e.is::<RateLimitedError>() || e.is::<SecondaryRateLimitedError>() {
+            Some(EXIT_RATE_LIMITED)
+        } else {
+            None
+        }
+    });
+    code.map_or(ExitCode::FAILURE, ExitCode::from)
+}
 
-    let repos_req = match token {
-        Some(_) => get_all_repos(&client).await,
-        None => get_public_repos(&client, args.username).await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>),
+/// Loads the config file, if any, into `args`.
+fn configure(args: &mut Args, matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    args.count_given = matches.value_source("count") == Some(ValueSource::CommandLine);
+    let config = match &args.config {
+        Some(path) => Some(Config::load(path)?),
+        None => Config::load_default()?,
     };
-    let repos = repos_req.expect("Failed to retrieve repositories.");
+    if let Some(config) = config {
+        apply_config(args, matches, config)?;
+    }
+    Ok(())
+}
 
-    println!("Choosing issue from {} repositories...", repos.len());
-    let filtered_repos = repos
-        .iter()
-        .filter(|repo| repo.has_issues && repo.open_issues > 0)
-        .filter(|repo| args.include_forked_repos || !repo.fork)
-        .collect::<Vec<_>>();
-    let repo = filtered_repos
-        .choose(&mut rand::thread_rng())
-        .expect("No viable repos to choose issues from.");
-    let issues = get_issues(&client, &repo)
+async fn run(mut args: Args) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let explicit_token = match (args.token.take(), &args.token_file) {
+        (Some(token), _) => Some(token),
+        (None, Some(path)) => Some(read_token_file(path)?),
+        (None, None) => None,
+    };
+    if args.no_color {
+        anstream::ColorChoice::Never.write_global();
+    }
+    if !std::io::stdout().is_terminal() {
+        args.no_emoji = true;
+    }
+    if args.record.is_some() || args.replay.is_some() {
+        args.no_cache = true;
+    }
+    let log_directives = match args.log_level {
+        Some(level) => vec![(None, level.into())],
+        None => std::env::var("RUST_LOG")
+            .map(|spec| parse_log_directives(&spec))
+            .unwrap_or_else(|_| vec![(None, LevelFilter::Warn)]),
+    };
+    init_logger(log_directives);
+
+    // Repositories without open issues would never be picked, however many closed ones they have.
+    if args.weighted && args.state != IssueState::Open {
+        return Err("--weighted requires --state open, it weights by the open issue count.".into());
+    }
+
+    let http_options = HttpOptions {
+        connect_timeout: args.connect_timeout.min(args.timeout),
+        proxy: args.proxy.clone(),
+        user_agent: args.user_agent.clone(),
+    };
+    let app = match (args.app_id, &args.private_key_file, args.installation_id) {
+        (Some(app_id), Some(path), Some(installation_id)) => {
+            let credentials = AppCredentials::load(app_id, installation_id, path)?;
+            let http = build_http_client(&None, &http_options)
+                .map_err(|e| format!("Failed to build http client: {}", e))?;
+            let token =
+                create_installation_token(&http, &args.base_url, &credentials, args.timeout)
+                    .await?;
+            Some((credentials, token))
+        }
+        _ => None,
+    };
+    let explicit_token = match &app {
+        Some((_, token)) => Some(token.token.clone()),
+        None => explicit_token,
+    };
+    let token = get_token(explicit_token)
+        .map_err(|e| format!("Failed to build Auth token header: {}", e))?;
+    let http = build_http_client(&token, &http_options)
+        .map_err(|e| format!("Failed to build http client: {}", e))?;
+    // Without the token, which is none of the webhook's business.
+    let notifier = match &args.notify_url {
+        Some(_) => Some(
+            build_http_client(&None, &http_options)
+                .map_err(|e| format!("Failed to build http client: {}", e))?,
+        ),
+        None => None,
+    };
+    if args.assign_me && token.is_none() {
+        return Err("--assign-me requires a token, see --token.".into());
+    }
+    // Installation tokens have no user of their own to stand in for.
+    if app.is_some() {
+        if args.assign_me {
+            return Err(
+                "--assign-me can't be used with --app-id, an installation has no user to assign."
+                    .into(),
+            );
+        }
+        if args.graphql {
+            return Err(
+                "--graphql can't be used with --app-id, it lists the user's own repositories."
+                    .into(),
+            );
+        }
+        let has_source = args.username.is_some() || args.org.is_some() || args.repo.is_some();
+        for (given, flag) in [(args.search, "--search"), (args.starred, "--starred")] {
+            if given && !has_source {
+                return Err(format!(
+                    "{} with --app-id requires --username, --org or --repo.",
+                    flag
+                )
+                .into());
+            }
+        }
+    }
+    if args.graphql && token.is_none() {
+        return Err(
+            "--graphql requires a token, GitHub doesn't allow anonymous GraphQL queries.".into(),
+        );
+    }
+    if args.graphql
+        && args
+            .issues_per_repo
+            .is_some_and(|limit| limit as usize > MAX_GRAPHQL_ISSUES_PER_REPO)
+    {
+        return Err(format!(
+            "--issues-per-repo can be at most {} with --graphql.",
+            MAX_GRAPHQL_ISSUES_PER_REPO
+        )
+        .into());
+    }
+    if args.private_only && token.is_none() {
+        return Err(
+            "--private-only requires a token, private repositories aren't visible without one."
+                .into(),
+        );
+    }
+
+    if args.verbose {
+        match token {
+            Some(_) => {
+                eprintln!("Token detected, authenticated rate limit is 5000 requests per hour.")
+            }
+            None => eprintln!("No token detected, anonymous rate limit is 60 requests per hour."),
+        }
+    }
+
+    let mut client = GitHub::new(http)
+        .with_base_url(args.base_url.clone())
+        .with_verbose(args.verbose)
+        .with_timeout(args.timeout)
+        .with_max_retries(args.max_retries)
+        .with_max_pages(args.max_pages)
+        .with_concurrency(args.concurrency);
+    if !args.no_cache {
+        client = client.with_cache(ResponseCache::load_default());
+    }
+    // Installation tokens carry neither a user nor scopes to check.
+    let is_app = app.is_some();
+    if let Some((credentials, token)) = app {
+        client = client.with_app(credentials, token);
+    }
+    if let Some(path) = &args.replay {
+        client = client.with_replay(Recording::load(path)?);
+    } else if let Some(path) = &args.record {
+        client = client.with_recording(path.clone());
+    }
+
+    match &args.command {
+        Some(Diagnostic::Auth {
+            command: AuthCommand::Check,
+        }) => return auth_check(&args, &client, token.is_some(), is_app).await,
+        Some(Diagnostic::Limit) => return show_rate_limits(&args, &client).await,
+        None => {}
+    }
+
+    if token.is_some() && !is_app {
+        let info = get_token_info(&client)
+            .await
+            .map_err(|e| ContextError::new("Failed to check the token", e))?;
+        warn_missing_scopes(&args, info);
+    }
+
+    let context = history_context(&args);
+    let mut last_runs = args.new_only.then(LastRuns::load_default);
+    let since = last_runs
+        .as_ref()
+        .and_then(|last_runs| last_runs.get(&context));
+    let started_at = Timestamp::now();
+    let result = match resolve_me(&mut args, &client, token.is_some()).await {
+        Ok(()) => roll(&args, &client, notifier.as_ref(), token.as_ref(), since).await,
+        Err(e) => Err(e),
+    };
+    if let (Some(last_runs), Ok(_)) = (&mut last_runs, &result) {
+        last_runs.record(&context, started_at);
+        if let Err(e) = last_runs.save() {
+            eprintln!("Warning: failed to write last run: {}", e);
+        }
+    }
+    if let Err(e) = client.save_cache() {
+        eprintln!("Warning: failed to write response cache: {}", e);
+    }
+    if let Err(e) = client.save_recording() {
+        eprintln!("Warning: {}", e);
+    }
+    result
+}
+
+/// Prints who the token authenticates as, its scopes and the rate limit left.
+async fn auth_check(
+    args: &Args,
+    client: &GitHub,
+    has_token: bool,
+    is_app: bool,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    if !has_token {
+        return Err(format!(
+            "No token found, pass --token or --token-file or set one of {}.",
+            TOKEN_ENV_VARS.join(", ")
+        )
+        .into());
+    }
+    // Installation tokens have no user to look up.
+    let info = match is_app {
+        true => None,
+        false => Some(
+            get_token_info(client)
+                .await
+                .map_err(|e| ContextError::new("The token doesn't work", e))?,
+        ),
+    };
+    let limits = get_rate_limits(client)
         .await
-        .expect("Failed to retrieve issues.");
-    let issue = issues
-        .choose(&mut rand::thread_rng())
-        .expect("No viable issue found.");
-    println!("🌟🦄 {} 🦄🌟", issue);
-}
-
-fn build_http_client(token: &Option<HeaderValue>) -> Result<reqwest::Client, reqwest::Error> {
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        "Accept",
-        HeaderValue::from_static("application/vnd.github+json"),
+        .map_err(|e| ContextError::new("Failed to look up the rate limit", e))?;
+
+    if args.format == OutputFormat::Json {
+        let output = serde_json::json!({
+            "login": info.map(|info| &info.login),
+            "scopes": info.and_then(|info| info.scopes.as_ref()),
+            "rate_limit": limits.core,
+        });
+        println!("{}", output);
+        return Ok(ExitCode::SUCCESS);
+    }
+    match info {
+        Some(info) => println!("Authenticated as {}.", info.login),
+        None => println!("Authenticated as a GitHub App installation."),
+    }
+    match info.map(|info| info.scopes.as_deref()) {
+        Some(Some([])) => println!("Scopes: none"),
+        Some(Some(scopes)) => println!("Scopes: {}", scopes.join(", ")),
+        Some(None) => println!("Scopes: not exposed, as by fine-grained tokens"),
+        None => {}
+    }
+    println!(
+        "Rate limit: {} of {} requests left, resets at {}",
+        limits.core.remaining,
+        limits.core.limit,
+        limits.core.reset_at().format_local()
     );
-    headers.insert(
-        "X-Github-Api-Version",
-        HeaderValue::from_static("2022-11-28"),
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the budgets of the REST API and of searches.
+async fn show_rate_limits(
+    args: &Args,
+    client: &GitHub,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let limits = get_rate_limits(client)
+        .await
+        .map_err(|e| ContextError::new("Failed to look up the rate limit", e))?;
+    if args.format == OutputFormat::Json {
+        let output = serde_json::json!({ "core": limits.core, "search": limits.search });
+        println!("{}", output);
+        return Ok(ExitCode::SUCCESS);
+    }
+    let now = Timestamp::now();
+    for (name, budget) in [("Requests", limits.core), ("Searches", limits.search)] {
+        let minutes = now.age(budget.reset_at()).as_secs().div_ceil(60);
+        println!(
+            "{}: {} of {} left, resets at {} (in {} min)",
+            name,
+            budget.remaining,
+            budget.limit,
+            budget.reset_at().format_local(),
+            minutes
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Warns about scopes the token lacks for what `args` asks for, before the first request that
+/// would fail or silently see less because of it.
+fn warn_missing_scopes(args: &Args, info: &TokenInfo) {
+    if info.scopes.is_none() {
+        log::debug!("The token doesn't expose its scopes, assuming it has the permissions needed");
+        return;
+    }
+    let has_repo = info.has_scope("repo");
+    if !has_repo && !args.public_only {
+        eprintln!("Warning: the token lacks the repo scope, so private repositories and their issues are left out.");
+    }
+    if !has_repo && !info.has_scope("public_repo") && args.assign_me {
+        eprintln!(
+            "Warning: the token lacks the repo and public_repo scopes that --assign-me needs."
+        );
+    }
+}
+
+/// Stands for the login of the token's user in --assigned-to and --issue-author.
+const ME: &str = "@me";
+
+/// Without a source or a token there is no "your own repositories" to fall back to.
+const NOTHING_TO_ROLL_ON: &str = "Nothing to roll on: pass --username, --org or --repo, or \
+                                  supply a token to use your own repositories.";
+
+/// Replaces [`ME`] in the login filters by the token's user, looked up once for both.
+async fn resolve_me(
+    args: &mut Args,
+    client: &GitHub,
+    authenticated: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for login in [&mut args.assigned_to, &mut args.issue_author]
+        .into_iter()
+        .flatten()
+    {
+        if login.as_str() != ME {
+            continue;
+        }
+        if !authenticated {
+            return Err(format!("{} requires a token, see --token.", ME).into());
+        }
+        if client.is_app() {
+            return Err(format!(
+                "{} can't be used with --app-id, an installation has no user.",
+                ME
+            )
+            .into());
+        }
+        *login = get_authenticated_user(client)
+            .await
+            .map_err(|e| ContextError::new("Failed to look up the authenticated user", e))?;
+    }
+    Ok(())
+}
+
+fn parse_seconds(text: &str) -> Result<Duration, String> {
+    let seconds = text
+        .parse::<f64>()
+        .map_err(|_| format!("'{}' is not a number of seconds", text))?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+fn parse_non_negative(text: &str) -> Result<f64, String> {
+    let value = text.parse().unwrap_or(f64::NAN);
+    check_non_negative(value).map_err(|_| format!("'{}' is not a number of at least 0", text))
+}
+
+fn check_non_negative(value: f64) -> Result<f64, String> {
+    if value >= 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(format!("{} is not a number of at least 0", value))
+    }
+}
+
+fn parse_positive(text: &str) -> Result<f64, String> {
+    let value = text.parse().unwrap_or(f64::NAN);
+    check_positive(value).map_err(|_| format!("'{}' is not a number greater than 0", text))
+}
+
+fn check_positive(value: f64) -> Result<f64, String> {
+    if value > 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(format!("{} is not a number greater than 0", value))
+    }
+}
+
+fn parse_duration_arg(text: &str) -> Result<Duration, String> {
+    parse_duration(text)
+        .ok_or_else(|| format!("'{}' is not a duration like 90s, 15m, 12h or 1d", text))
+}
+
+/// Fills in every setting that wasn't given on the command line from `config`.
+fn apply_config(
+    args: &mut Args,
+    matches: &ArgMatches,
+    config: Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for key in config.unknown.keys() {
+        eprintln!("Warning: ignoring unknown config key '{}'.", key);
+    }
+
+    let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    args.count_given |= config.count.is_some();
+    // The settings taken from the config, less switches turned off, to check for conflicts.
+    let mut from_config = Vec::new();
+    macro_rules! merge {
+        ($($field:ident),* $(,)?) => {$(
+            if let Some(value) = config.$field {
+                if !from_cli(stringify!($field)) {
+                    if (&value as &dyn std::any::Any).downcast_ref() != Some(&false) {
+                        from_config.push(stringify!($field));
+                    }
+                    args.$field = value.into();
+                }
+            }
+        )*};
+    }
+    merge!(
+        username,
+        org,
+        repos_file,
+        include_forked_repos,
+        forks_only,
+        include_archived,
+        private_only,
+        public_only,
+        starred,
+        min_issues,
+        min_stars,
+        language,
+        topics,
+        include_repo,
+        exclude_repo,
+        token_file,
+        app_id,
+        private_key_file,
+        installation_id,
+        user_agent,
+        max_retries,
+        max_pages,
+        include_prs,
+        exclude_drafts,
+        labels,
+        exclude_labels,
+        prefer_labels,
+        good_first_issue,
+        count,
+        count_per_repo,
+        no_color,
+        no_emoji,
+        output,
+        notify_url,
+        verbose,
+        quiet,
+        no_cache,
+        all_issues,
+        max_repos,
+        issues_per_repo,
+        concurrency,
+        dedup,
+        shuffle,
+        explain,
+        interactive,
+        no_history,
+        history_days,
+        open,
+        weighted,
+        new_only,
+        issue_author,
+        milestone,
+        no_milestone,
+        min_comments,
+        max_comments,
+        min_reactions,
+        include_assigned,
+        assigned_to,
+    );
+
+    if let Some(base_url) = config.base_url.filter(|_| !from_cli("base_url")) {
+        from_config.push("base_url");
+        args.base_url = parse_base_url(&base_url).map_err(|e| format!("Invalid config: {}", e))?;
+    }
+    if let Some(state) = config.state.filter(|_| !from_cli("state")) {
+        from_config.push("state");
+        args.state =
+            IssueState::from_str(&state, true).map_err(|e| format!("Invalid config: {}", e))?;
+    }
+    if let Some(affiliation) = config.affiliation.filter(|_| !from_cli("affiliation")) {
+        from_config.push("affiliation");
+        args.affiliation = affiliation
+            .iter()
+            .map(|value| Affiliation::from_str(value, true))
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Invalid config: {}", e))?;
+    }
+    if let Some(bias) = config.bias.filter(|_| !from_cli("bias")) {
+        from_config.push("bias");
+        args.bias = Bias::from_str(&bias, true).map_err(|e| format!("Invalid config: {}", e))?;
+    }
+    if let Some(weight) = config
+        .reaction_weight
+        .filter(|_| !from_cli("reaction_weight"))
+    {
+        from_config.push("reaction_weight");
+        args.reaction_weight =
+            check_non_negative(weight).map_err(|e| format!("Invalid config: {}", e))?;
+    }
+    if let Some(weight) = config.prefer_weight.filter(|_| !from_cli("prefer_weight")) {
+        from_config.push("prefer_weight");
+        args.prefer_weight =
+            check_positive(weight).map_err(|e| format!("Invalid config: {}", e))?;
+    }
+    if let Some(days) = config.stale_days.filter(|_| !from_cli("stale_days")) {
+        from_config.push("stale_days");
+        args.stale_days = check_positive(days).map_err(|e| format!("Invalid config: {}", e))?;
+    }
+    if let Some(cache_ttl) = config.cache_ttl.filter(|_| !from_cli("cache_ttl")) {
+        from_config.push("cache_ttl");
+        args.cache_ttl =
+            parse_duration_arg(&cache_ttl).map_err(|e| format!("Invalid config: {}", e))?;
+    }
+    if let Some(log_level) = config.log_level.filter(|_| !from_cli("log_level")) {
+        from_config.push("log_level");
+        args.log_level = Some(
+            LogLevel::from_str(&log_level, true).map_err(|e| format!("Invalid config: {}", e))?,
+        );
+    }
+    if let Some(format) = config.format.filter(|_| !from_cli("format")) {
+        from_config.push("format");
+        args.format =
+            OutputFormat::from_str(&format, true).map_err(|e| format!("Invalid config: {}", e))?;
+    }
+    if let Some(format) = config.notify_format.filter(|_| !from_cli("notify_format")) {
+        from_config.push("notify_format");
+        args.notify_format =
+            NotifyFormat::from_str(&format, true).map_err(|e| format!("Invalid config: {}", e))?;
+    }
+    if let Some(sort) = config.sort.filter(|_| !from_cli("sort")) {
+        from_config.push("sort");
+        args.sort =
+            Some(SortKey::from_str(&sort, true).map_err(|e| format!("Invalid config: {}", e))?);
+    }
+    if let Some(order) = config.order.filter(|_| !from_cli("order")) {
+        from_config.push("order");
+        args.order =
+            SortOrder::from_str(&order, true).map_err(|e| format!("Invalid config: {}", e))?;
+    }
+    if let Some(filter) = config.repo_filter.filter(|_| !from_cli("repo_filter")) {
+        from_config.push("repo_filter");
+        args.repo_filter = Some(
+            filter
+                .parse::<RepoFilterExpr>()
+                .map_err(|e| format!("Invalid config: {}", e))?,
+        );
+    }
+    if let Some(template) = config.template.filter(|_| !from_cli("template")) {
+        from_config.push("template");
+        args.template = Some(
+            template
+                .parse::<Template>()
+                .map_err(|e| format!("Invalid config: {}", e))?,
+        );
+    }
+    check_config_conflicts(matches, &from_config)?;
+    Ok(())
+}
+
+/// Rejects settings from the config that conflict with each other or with flags given on the
+/// command line, which clap only checks among the flags themselves.
+fn check_config_conflicts(matches: &ArgMatches, from_config: &[&str]) -> Result<(), String> {
+    let command = Args::command();
+    // Conflicts are declared on either of the two arguments.
+    let conflict = |a: &clap::Arg, b: &clap::Arg| {
+        let declared = |a: &clap::Arg, b: &clap::Arg| {
+            command
+                .get_arg_conflicts_with(a)
+                .iter()
+                .any(|arg| arg.get_id() == b.get_id())
+        };
+        declared(a, b) || declared(b, a)
+    };
+    for id in from_config {
+        let Some(arg) = command.get_arguments().find(|arg| arg.get_id() == id) else {
+            continue;
+        };
+        for other in command.get_arguments().filter(|other| conflict(arg, other)) {
+            let other_id = other.get_id().as_str();
+            if matches.value_source(other_id) == Some(ValueSource::CommandLine) {
+                return Err(format!(
+                    "Invalid config: '{}' can't be used with --{}.",
+                    id,
+                    other.get_long().unwrap_or(other_id)
+                ));
+            }
+            if from_config.contains(&other_id) {
+                return Err(format!(
+                    "Invalid config: '{}' and '{}' can't be used together.",
+                    id, other_id
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn roll(
+    args: &Args,
+    client: &GitHub,
+    notifier: Option<&reqwest::Client>,
+    token: Option<&HeaderValue>,
+    since: Option<Timestamp>,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let authenticated = token.is_some();
+    let mut rng = match args.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    let issue_query = IssueQuery {
+        state: args.state,
+        since,
+        limit: args.issues_per_repo.map(|limit| limit as usize),
+    };
+    let filters = issue_filters(args, since);
+    // The spinner would garble verbose logs and has no place next to machine-readable output.
+    let quiet_spinner = args.verbose
+        || args.quiet
+        || log::log_enabled!(log::Level::Info)
+        || args.format == OutputFormat::Json;
+    let spinner = |message: String| {
+        if quiet_spinner {
+            Spinner::hidden()
+        } else {
+            Spinner::start(message)
+        }
+    };
+
+    let mut search_results = Vec::new();
+    let mut prefetched = HashMap::new();
+    // How the repository to choose from was picked, unless issues are pooled across them.
+    let mut repo_pick = None;
+    let mut candidates = if args.search {
+        let query = search_query(args, client, authenticated, since).await?;
+        let progress = spinner("Searching issues...".to_string());
+        let (issues, total_count) = search_issues(client, &query)
+            .await
+            .map_err(|e| ContextError::new("Failed to search issues", e))?;
+        progress.finish();
+        if total_count > MAX_SEARCH_RESULTS {
+            note!(
+                args,
+                "Search matched {} issues, only the first {} are considered.",
+                total_count,
+                MAX_SEARCH_RESULTS
+            );
+        }
+
+        let mut names = issues
+            .iter()
+            .map(|issue| issue.repository_name().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        search_results = issues;
+        names.into_iter().map(Repo::named).collect()
+    } else if let Some(path) = &args.repos_file {
+        read_repos_file(path)?
+            .into_iter()
+            .map(Repo::named)
+            .collect()
+    } else if let Some(full_name) = &args.repo {
+        vec![get_repo(client, full_name).await?]
+    } else {
+        let mut repo_cache = (!args.no_cache && !args.graphql).then(RepoListCache::load_default);
+        let key = repo_list_key(args, token);
+        let cached = repo_cache
+            .as_ref()
+            .filter(|_| !args.refresh)
+            .and_then(|cache| cache.get(&key, args.cache_ttl))
+            .map(<[Repo]>::to_vec);
+        let repos = match cached {
+            Some(repos) => repos,
+            None => {
+                let progress = spinner("Fetching repositories...".to_string());
+                let repos_req = match (&args.org, &args.username) {
+                    (Some(org), _) => get_org_repos(client, org).await,
+                    (None, Some(username)) if args.starred => {
+                        get_starred_repos(client, Some(username)).await
+                    }
+                    (None, Some(username)) => get_public_repos(client, username).await,
+                    (None, None) if !authenticated => {
+                        return Err(NOTHING_TO_ROLL_ON.into());
+                    }
+                    (None, None) if client.is_app() => get_installation_repos(client).await,
+                    (None, None) if args.starred => get_starred_repos(client, None).await,
+                    (None, None) if args.graphql => {
+                        let repos =
+                            get_repos_with_issues(client, &args.affiliation, &issue_query).await;
+                        repos.map(|repos| {
+                            let mut names = Vec::new();
+                            for (repo, issues) in repos {
+                                prefetched.insert(repo.full_name.clone(), issues);
+                                names.push(repo);
+                            }
+                            names
+                        })
+                    }
+                    (None, None) => get_all_repos(client, &args.affiliation).await,
+                };
+                let repos = repos_req
+                    .map_err(|e| ContextError::new("Failed to retrieve repositories", e))?;
+                progress.finish();
+                if let Some(cache) = &mut repo_cache {
+                    cache.insert(key, repos.clone());
+                    if let Err(e) = cache.save() {
+                        eprintln!("Warning: failed to write repository cache: {}", e);
+                    }
+                }
+                repos
+            }
+        };
+
+        note!(args, "Choosing issue from {} repositories...", repos.len());
+        filter_repos(args, repos)
+    };
+    if let Some(max_repos) = args.max_repos.filter(|max| candidates.len() > *max) {
+        note!(
+            args,
+            "Sampling {} of {} repositories, see --max-repos.",
+            max_repos,
+            candidates.len()
+        );
+        candidates = sample_repos(candidates, max_repos, &mut rng);
+    }
+    // Balanced sampling spreads --count over that many repositories.
+    if args.count_per_repo.is_some() && candidates.len() > args.count {
+        candidates = sample_repos(candidates, args.count, &mut rng);
+    }
+
+    let (source, pool) = if args.search {
+        let pool = search_results
+            .into_iter()
+            .filter_map(|issue| {
+                let repo = candidates
+                    .iter()
+                    .find(|repo| repo.full_name == issue.repository_name())?;
+                Some((repo, issue))
+            })
+            .collect();
+        (format!("{} repositories", candidates.len()), pool)
+    } else if args.graphql {
+        let pool = candidates
+            .iter()
+            .flat_map(|repo| {
+                let issues = prefetched.remove(&repo.full_name).unwrap_or_default();
+                issues.into_iter().map(move |issue| (repo, issue))
+            })
+            .collect();
+        (format!("{} repositories", candidates.len()), pool)
+    } else if args.all_issues
+        || args.list
+        || args.count_per_repo.is_some()
+        || args.repos_file.is_some()
+    {
+        let total = candidates.len();
+        if total > MANY_REPOS && args.max_repos.is_none() {
+            note!(
+                args,
+                "Fetching the issues of {} repositories, pass --max-repos to make fewer requests.",
+                total
+            );
+        }
+        let progress = spinner(format!("Fetching issues 0/{}", total));
+        let pool = get_issues_for_repos(
+            client,
+            &candidates,
+            &issue_query,
+            args.concurrency,
+            args.timeout_per_repo,
+            |done| progress.set_message(format!("Fetching issues {}/{}", done, total)),
+        )
+        .await
+        .map_err(|e| ContextError::new("Failed to retrieve issues", e))?;
+        progress.finish();
+        (format!("{} repositories", candidates.len()), pool)
+    } else {
+        let mut remaining = candidates.iter().collect::<Vec<_>>();
+        let mut attempts = 0;
+        // Set while every repository tried so far was forbidden.
+        let mut forbidden = None;
+        loop {
+            let Some(repo) = select_repo(&remaining, args.weighted, &mut rng) else {
+                if let Some(e) = forbidden {
+                    return Err(e);
+                }
+                eprintln!("No viable repos to choose issues from.");
+                return Ok(ExitCode::from(EXIT_NOTHING_FOUND));
+            };
+            repo_pick = Some(RepoPick::of(repo, &remaining, args.weighted));
+            let progress = spinner(format!("Fetching issues of {}...", repo));
+            let issues = match get_issues(client, repo, &issue_query).await {
+                Err(e) if e.is::<IssuesDisabledError>() => {
+                    progress.finish();
+                    eprintln!("{}", e);
+                    if args.repo.is_some() {
+                        return Ok(ExitCode::from(EXIT_NOTHING_FOUND));
+                    }
+                    remaining.retain(|other| !std::ptr::eq(*other, repo));
+                    continue;
+                }
+                Err(e) if e.is::<IssuesForbiddenError>() && args.repo.is_none() => {
+                    progress.finish();
+                    eprintln!("Warning: {} Trying another repository.", e);
+                    remaining.retain(|other| !std::ptr::eq(*other, repo));
+                    forbidden = Some(e);
+                    continue;
+                }
+                issues => issues.map_err(|e| {
+                    ContextError::new(format!("Failed to retrieve issues for {}", repo), e)
+                })?,
+            };
+            progress.finish();
+            let pool = issues
+                .into_iter()
+                .map(|issue| (repo, issue))
+                .collect::<Vec<_>>();
+
+            forbidden = None;
+
+            // Open issue counts include pull requests, so a repository may well have nothing left.
+            attempts += 1;
+            remaining.retain(|other| !std::ptr::eq(*other, repo));
+            let viable = pool.iter().any(|(_, issue)| is_candidate(&filters, issue));
+            if viable || attempts == MAX_REPO_ATTEMPTS || remaining.is_empty() {
+                break (repo.to_string(), pool);
+            }
+            note!(
+                args,
+                "No viable issue found in {}, trying another repository.",
+                repo
+            );
+        }
+    };
+
+    let mut issues = pool.iter().collect::<Vec<_>>();
+    let mut funnel = Funnel::new(format!("issues in {}", source), issues.len());
+    for (name, keep) in &filters {
+        issues.retain(|(_, issue)| keep(issue));
+        funnel.record(name, issues.len());
+    }
+    // Search results and retried pages may list an issue twice.
+    let mut seen = HashSet::new();
+    issues.retain(|(_, issue)| seen.insert(issue.html_url.as_str()));
+    funnel.record("distinct", issues.len());
+    funnel.report(args);
+    if issues.is_empty() {
+        if let Some(since) = since.filter(|_| funnel.narrowed_by(NEW_SINCE_LAST_RUN)) {
+            eprintln!(
+                "No issue in {} was updated since the last run at {}.",
+                source, since
+            );
+        } else if args.good_first_issue {
+            eprintln!("No good first issue found in {}.", source);
+        } else if args.labels.is_empty() {
+            eprintln!("No viable issue found in {}.", source);
+        } else {
+            eprintln!(
+                "No issue in {} carries all of the labels: {}.",
+                source,
+                args.labels.join(", ")
+            );
+        }
+        let skipped_assigned = pool.iter().any(|(_, issue)| !issue.assignees.is_empty());
+        if args.assigned_to.is_none() && !args.include_assigned && skipped_assigned {
+            note!(args, "Issues already assigned to someone were skipped, pass --include-assigned to consider them.");
+        }
+        return Ok(ExitCode::from(EXIT_NOTHING_FOUND));
+    }
+
+    if args.list {
+        if let Some(key) = args.sort {
+            sort_issues(&mut issues, key, args.order);
+        }
+        if args.shuffle {
+            issues.shuffle(&mut rng);
+            if args.count_given {
+                issues.truncate(args.count);
+            }
+        }
+        note!(args, "{} candidate issues in {}:", issues.len(), source);
+        for (repo, issue) in issues {
+            match (&args.template, args.format) {
+                (Some(template), _) => println!("{}", template.render(repo, issue)),
+                (None, OutputFormat::Human) => writeln!(
+                    anstream::stdout(),
+                    "{} {}{}",
+                    repo,
+                    decorate(issue),
+                    render_labels(issue)
+                )?,
+                (None, OutputFormat::Markdown) => println!("{}", markdown(repo, issue)),
+                (None, OutputFormat::Json) => {
+                    println!("{}", serde_json::to_string(&Selection::new(repo, issue))?)
+                }
+            }
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut output = match &args.output {
+        Some(path) => Some(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("Failed to open output file {}: {}", path.display(), e))?,
+        ),
+        None => None,
+    };
+
+    let context = history_context(args);
+    let now = Timestamp::now();
+    // Saturating, so a huge --history-days means the whole history rather than an overflow.
+    let history_start = now - Duration::from_secs(args.history_days.saturating_mul(24 * 60 * 60));
+    let mut history = (!args.no_history).then(History::load_default);
+    if let Some(history) = &mut history {
+        if args.forget {
+            history.forget(&context);
+        }
+        history.prune(&context, history_start);
+
+        let recent = history.recent(&context, history_start);
+        let fresh = issues
+            .iter()
+            .filter(|(_, issue)| !recent.contains(issue.html_url.as_str()))
+            .copied()
+            .collect::<Vec<_>>();
+        if fresh.is_empty() {
+            note!(
+                args,
+                "Every candidate issue was chosen within the last {} days, ignoring the history.",
+                args.history_days
+            );
+        } else {
+            issues = fresh;
+        }
+    }
+
+    if args.interactive && std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+        if let Some(key) = args.sort {
+            sort_issues(&mut issues, key, args.order);
+        }
+        let entries = issues
+            .iter()
+            .map(|(repo, issue)| format!("{} {}{}", repo, decorate(issue), render_labels(issue)))
+            .collect::<Vec<_>>();
+        issues = match pick(&entries)? {
+            Pick::Entry(index) => vec![issues[index]],
+            Pick::Random(shown) => shown.into_iter().map(|index| issues[index]).collect(),
+            Pick::Quit => return Ok(ExitCode::from(EXIT_NOTHING_FOUND)),
+        };
+    }
+
+    let (mut chosen, requested) = match args.count_per_repo {
+        Some(per_repo) => {
+            let mut chosen = Vec::new();
+            for group in issues.chunk_by(|a, b| std::ptr::eq(a.0, b.0)) {
+                chosen.extend(select_issues(group, per_repo, args, now, &mut rng)?);
+            }
+            (chosen, per_repo * candidates.len())
+        }
+        None => (
+            select_issues(&issues, args.count, args, now, &mut rng)?,
+            args.count,
+        ),
+    };
+    if let Some(key) = args.sort {
+        sort_issues(&mut chosen, key, args.order);
+    }
+    if let Some(history) = &mut history {
+        for (_, issue) in &chosen {
+            history.record(&context, issue.html_url.clone(), now);
+        }
+        if let Err(e) = history.save() {
+            eprintln!("Warning: failed to write history: {}", e);
+        }
+    }
+    if args.explain {
+        eprintln!("{}", explain(args, &issues, &chosen, repo_pick, now));
+    }
+    if chosen.len() < requested {
+        note!(
+            args,
+            "Only {} of {} requested issues available in {}.",
+            chosen.len(),
+            requested,
+            source
+        );
+    }
+    let login = if args.assign_me {
+        Some(get_authenticated_user(client).await?)
+    } else {
+        None
+    };
+    for (repo, issue) in chosen {
+        let line = match (&args.template, args.format) {
+            (Some(template), _) => template.render(repo, issue),
+            (None, OutputFormat::Human) if args.no_emoji => {
+                format!(">>> {} {} <<<", repo, decorate(issue))
+            }
+            (None, OutputFormat::Human) => format!("🌟🦄 {} {} 🦄🌟", repo, decorate(issue)),
+            (None, OutputFormat::Json) => serde_json::to_string(&Selection::new(repo, issue))?,
+            (None, OutputFormat::Markdown) => markdown(repo, issue),
+        };
+        writeln!(anstream::stdout(), "{}", line)?;
+        if let (Some(file), Some(path)) = (&mut output, &args.output) {
+            writeln!(file, "{}", anstream::adapter::strip_str(&line))
+                .map_err(|e| format!("Failed to write output file {}: {}", path.display(), e))?;
+        }
+        if let Some(login) = &login {
+            match assign_issue(client, repo, issue, login).await {
+                Ok(()) => note!(args, "Assigned {}#{} to {}.", repo, issue.number, login),
+                Err(e) => eprintln!("Could not assign {}#{}: {}", repo, issue.number, e),
+            }
+        }
+        if let (Some(http), Some(url)) = (notifier, &args.notify_url) {
+            let payload = notification(args.notify_format, repo, issue);
+            if let Err(e) = post_webhook(http, url, &payload, args.timeout).await {
+                eprintln!("Warning: could not notify {}: {}", url, e);
+            }
+        }
+        if args.open && !args.no_open {
+            if let Err(e) = open_in_browser(&issue.html_url) {
+                eprintln!(
+                    "Warning: could not open {} in a browser: {}",
+                    issue.html_url, e
+                );
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// How the one repository issues were chosen from was picked, for --explain.
+#[derive(Clone, Copy, Debug)]
+enum RepoPick {
+    Uniform { among: usize },
+    Weighted { open_issues: u32, total: u64 },
+}
+
+impl RepoPick {
+    fn of(repo: &Repo, remaining: &[&Repo], weighted: bool) -> Self {
+        if weighted {
+            RepoPick::Weighted {
+                open_issues: repo.open_issues,
+                total: remaining
+                    .iter()
+                    .map(|repo| u64::from(repo.open_issues))
+                    .sum(),
+            }
+        } else {
+            RepoPick::Uniform {
+                among: remaining.len(),
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for RepoPick {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            RepoPick::Uniform { among } => write!(
+                f,
+                "the repository was picked uniformly among {}, a {:.1}% chance",
+                among,
+                100.0 / among as f64
+            ),
+            RepoPick::Weighted { open_issues, total } => write!(
+                f,
+                "the repository was picked by its {} of {} open issues, a {:.1}% chance",
+                open_issues,
+                total,
+                100.0 * f64::from(open_issues) / total as f64
+            ),
+        }
+    }
+}
+
+/// Describes how `chosen` came out of the `candidates`, one line per weighted issue.
+fn explain(
+    args: &Args,
+    candidates: &[&(&Repo, Issue)],
+    chosen: &[&(&Repo, Issue)],
+    repo_pick: Option<RepoPick>,
+    now: Timestamp,
+) -> String {
+    let repos = candidates
+        .iter()
+        .map(|(repo, _)| repo.full_name.as_str())
+        .collect::<HashSet<_>>();
+    let mut text = format!(
+        "Chosen from {} candidate issues in {} repositories",
+        candidates.len(),
+        repos.len()
     );
+    if let Some(pick) = repo_pick {
+        text.push_str(&format!("; {}", pick));
+    }
+    if !is_weighted(args) {
+        let among = match args.count_per_repo {
+            Some(_) => "issues of its repository",
+            None => "candidates",
+        };
+        text.push_str(&format!(
+            "; every issue was picked uniformly among the {}.",
+            among
+        ));
+        return text;
+    }
+
+    let mut weighting = Vec::new();
+    if args.bias != Bias::None {
+        let bias = args.bias.to_possible_value().expect("no skipped variants");
+        weighting.push(format!("--bias {}", bias.get_name()));
+    }
+    if !args.prefer_labels.is_empty() {
+        weighting.push(format!(
+            "--prefer-label {} (×{})",
+            args.prefer_labels.join(", "),
+            args.prefer_weight
+        ));
+    }
+    text.push_str(&format!(
+        "; issues were weighted by {}:",
+        weighting.join(" and ")
+    ));
+    for (repo, issue) in chosen {
+        let weight = issue_weight(issue, now, args);
+        // Balanced sampling draws from each repository separately.
+        let total: f64 = candidates
+            .iter()
+            .filter(|(other, _)| args.count_per_repo.is_none() || std::ptr::eq(*other, *repo))
+            .map(|(_, issue)| issue_weight(issue, now, args))
+            .sum();
+        text.push_str(&format!(
+            "\n  {}#{}: weight {:.2} of {:.2} in total, a {:.1}% chance per draw",
+            repo,
+            issue.number,
+            weight,
+            total,
+            100.0 * weight / total
+        ));
+    }
+    text
+}
+
+/// How many repositories or issues survived each filter in turn, which explains a small pool.
+struct Funnel {
+    what: String,
+    total: usize,
+    stages: Vec<(&'static str, usize)>,
+}
+
+impl Funnel {
+    fn new(what: impl Into<String>, total: usize) -> Self {
+        Funnel {
+            what: what.into(),
+            total,
+            stages: Vec::new(),
+        }
+    }
 
-    if let Some(token) = token {
-        headers.insert(reqwest::header::AUTHORIZATION, token.clone());
+    fn record(&mut self, stage: &'static str, left: usize) {
+        self.stages.push((stage, left));
     }
 
-    reqwest::Client::builder()
-        .user_agent("issue-roulette")
-        .default_headers(headers)
-        .build()
+    /// Whether the stage named `stage` removed anything.
+    fn narrowed_by(&self, stage: &str) -> bool {
+        let mut before = self.total;
+        self.stages.iter().any(|(name, left)| {
+            let narrowed = *name == stage && *left < before;
+            before = *left;
+            narrowed
+        })
+    }
+
+    /// Logs the funnel, and prints it to stderr with --verbose or when nothing survived
+    /// without --quiet.
+    fn report(&self, args: &Args) {
+        let left = self.stages.last().map_or(self.total, |(_, left)| *left);
+        if args.verbose || (left == 0 && !args.quiet) {
+            eprintln!("{}", self);
+        } else {
+            log::debug!("{}", self);
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-struct BadRequestError(u16, String);
-impl std::fmt::Display for BadRequestError {
+impl std::fmt::Display for Funnel {
+    /// Only stages that removed something are shown, e.g. `repos: 120 total → 98 with issues`.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[{}]: {}", self.0, self.1)
+        write!(f, "{}: {} total", self.what, self.total)?;
+        let mut before = self.total;
+        for (stage, left) in &self.stages {
+            if *left < before {
+                write!(f, " → {} {}", left, stage)?;
+            }
+            before = *left;
+        }
+        Ok(())
     }
 }
-impl std::error::Error for BadRequestError {}
 
-async fn get_all_repos(client: &reqwest::Client) -> Result<Vec<Repo>, Box<dyn std::error::Error>> {
-    let res = client
-        .get("https://api.github.com/user/repos?per_page=100")
-        .send()
-        .await?;
+/// A named predicate deciding whether a repository stays a candidate. Names describe the
+/// repositories that pass, as shown in the funnel.
+type RepoFilter<'a> = (&'static str, &'a dyn Fn(&Repo) -> bool);
+
+/// Applies the repository filters in turn, reporting how many repositories each one keeps.
+fn filter_repos(args: &Args, mut repos: Vec<Repo>) -> Vec<Repo> {
+    let forks = ForkFilter::from_args(args);
+    let filters: [RepoFilter; 12] = [
+        ("with issues", &|repo| repo.has_issues),
+        // The open issue count says nothing about closed issues.
+        ("with enough open issues", &|repo| {
+            args.state != IssueState::Open || repo.open_issues >= args.min_issues.max(1)
+        }),
+        ("with enough stars", &|repo| {
+            args.min_stars
+                .is_none_or(|min| repo.stargazers_count >= min)
+        }),
+        (
+            match forks {
+                ForkFilter::Only => "forks",
+                _ => "non-fork",
+            },
+            &|repo| forks.allows(repo),
+        ),
+        ("non-archived", &|repo| {
+            args.include_archived || !repo.archived
+        }),
+        ("private", &|repo| !args.private_only || repo.private),
+        ("public", &|repo| !args.public_only || !repo.private),
+        ("in the language", &|repo| {
+            args.language.as_ref().is_none_or(|language| {
+                repo.language
+                    .as_ref()
+                    .is_some_and(|repo_language| repo_language.eq_ignore_ascii_case(language))
+            })
+        }),
+        ("with the topics", &|repo| {
+            args.topics.iter().all(|topic| repo.has_topic(topic))
+        }),
+        ("included", &|repo| {
+            let matches = |pattern: &String| glob_match(pattern, &repo.full_name);
+            args.include_repo.is_empty() || args.include_repo.iter().any(matches)
+        }),
+        ("not excluded", &|repo| {
+            !args
+                .exclude_repo
+                .iter()
+                .any(|pattern| glob_match(pattern, &repo.full_name))
+        }),
+        ("matching --repo-filter", &|repo| {
+            args.repo_filter
+                .as_ref()
+                .is_none_or(|filter| filter.matches(repo))
+        }),
+    ];
 
-    let status = res.status();
-    if status != StatusCode::OK {
-        let text = res.text().await?;
-        return Err(Box::new(BadRequestError(status.as_u16(), text)));
+    let mut funnel = Funnel::new("repos", repos.len());
+    for (name, keep) in filters {
+        repos.retain(|repo| keep(repo));
+        funnel.record(name, repos.len());
     }
+    funnel.report(args);
+    repos
+}
 
-    let json = res.json::<Vec<Repo>>().await?;
-    Ok(json)
+/// Keeps a random sample of `count` of `repos`, in their original order.
+fn sample_repos<R: Rng>(repos: Vec<Repo>, count: usize, rng: &mut R) -> Vec<Repo> {
+    let mut keep = rand::seq::index::sample(rng, repos.len(), count).into_vec();
+    keep.sort_unstable();
+    keep.into_iter().map(|index| repos[index].clone()).collect()
 }
 
-async fn get_public_repos(
-    client: &reqwest::Client,
-    username: String,
-) -> Result<Vec<Repo>, reqwest::Error> {
-    client
-        .get(format!(
-            "https://api.github.com/users/{}/repos?per_page=100",
-            username
-        ))
-        .send()
-        .await?
-        .json::<Vec<Repo>>()
-        .await
+/// Picks one of `repos`, weighted by their open issues if `weighted`.
+fn select_repo<'a, R: Rng>(repos: &[&'a Repo], weighted: bool, rng: &mut R) -> Option<&'a Repo> {
+    let repo = if weighted {
+        repos.choose_weighted(rng, |repo| repo.open_issues).ok()
+    } else {
+        repos.choose(rng)
+    };
+    repo.copied()
 }
 
-async fn get_issues(client: &reqwest::Client, repo: &Repo) -> Result<Vec<Issue>, reqwest::Error> {
-    client
-        .get(format!(
-            "https://api.github.com/repos/{}/issues",
-            repo.full_name
-        ))
-        .send()
-        .await?
-        .json::<Vec<Issue>>()
-        .await
+/// Orders `issues` by `key`. Issues without a timestamp sort first in ascending order.
+fn sort_issues(issues: &mut [&(&Repo, Issue)], key: SortKey, order: SortOrder) {
+    issues.sort_by(|(_, a), (_, b)| {
+        let ordering = match key {
+            SortKey::Created => a.created_at.cmp(&b.created_at),
+            SortKey::Updated => a.updated_at.cmp(&b.updated_at),
+            SortKey::Comments => a.comments.cmp(&b.comments),
+            SortKey::Number => a.number.cmp(&b.number),
+        };
+        match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    });
 }
 
-fn get_token(
-    token: Option<String>,
-) -> Result<Option<HeaderValue>, reqwest::header::InvalidHeaderValue> {
-    if let Some(token) = token.or(std::env::var("ISSUE_ROULETTE_TOKEN").ok()) {
-        let value = HeaderValue::from_str(&format!("Bearer {}", token))?;
-        Ok(Some(value))
+/// Draws `count` issues from `pool` according to --bias and --prefer-label. With --dedup, issues whose title is
+/// similar to one drawn before are passed over, so fewer than `count` may come back.
+fn select_issues<'a, R: Rng>(
+    pool: &[&'a (&'a Repo, Issue)],
+    count: usize,
+    args: &Args,
+    now: Timestamp,
+    rng: &mut R,
+) -> Result<Vec<&'a (&'a Repo, Issue)>, Box<dyn std::error::Error>> {
+    // Drawing the whole pool orders it randomly, leaving enough to skip duplicates.
+    let draw = if args.dedup { pool.len() } else { count };
+    let drawn: Vec<_> = if !is_weighted(args) {
+        pool.choose_multiple(rng, draw).copied().collect()
     } else {
-        Ok(None)
+        pool.choose_multiple_weighted(rng, draw, |(_, issue)| issue_weight(issue, now, args))?
+            .copied()
+            .collect()
+    };
+    if !args.dedup {
+        return Ok(drawn);
+    }
+
+    let mut chosen: Vec<&(&Repo, Issue)> = Vec::new();
+    for candidate in drawn {
+        if chosen.len() == count {
+            break;
+        }
+        if !chosen
+            .iter()
+            .any(|(_, issue)| similar_titles(&issue.title, &candidate.1.title))
+        {
+            chosen.push(candidate);
+        }
+    }
+    Ok(chosen)
+}
+
+/// Whether `a` and `b` share at least 80% of their words, ignoring case and punctuation.
+fn similar_titles(a: &str, b: &str) -> bool {
+    let words = |title: &str| {
+        title
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect::<HashSet<_>>()
+    };
+    let (a, b) = (words(a), words(b));
+    let union = a.union(&b).count();
+    union == 0 || a.intersection(&b).count() * 5 >= union * 4
+}
+
+/// A named predicate deciding whether an issue stays a candidate, see [`RepoFilter`].
+type IssueFilter<'a> = (&'static str, Box<dyn Fn(&Issue) -> bool + 'a>);
+
+const NEW_SINCE_LAST_RUN: &str = "new since the last run";
+
+/// The issue filters in the order they are applied. With `since`, only issues updated since
+/// then pass.
+fn issue_filters(args: &Args, since: Option<Timestamp>) -> Vec<IssueFilter<'_>> {
+    vec![
+        (
+            "non-PR",
+            Box::new(|issue| args.include_prs || issue.pull_request.is_none()),
+        ),
+        (
+            "non-draft",
+            Box::new(|issue| !args.exclude_drafts || !issue.draft),
+        ),
+        (
+            "with the labels",
+            Box::new(|issue| args.labels.iter().all(|label| issue.has_label(label))),
+        ),
+        (
+            "without excluded labels",
+            Box::new(|issue| {
+                !args
+                    .exclude_labels
+                    .iter()
+                    .any(|label| issue.has_label(label))
+            }),
+        ),
+        (
+            "good first issues",
+            Box::new(|issue| {
+                !args.good_first_issue
+                    || GOOD_FIRST_ISSUE_LABELS
+                        .iter()
+                        .any(|label| issue.has_label(label))
+            }),
+        ),
+        (
+            "updated early enough",
+            Box::new(|issue| {
+                args.updated_before
+                    .is_none_or(|before| issue.updated_at.is_some_and(|updated| updated < before))
+            }),
+        ),
+        (
+            "updated recently enough",
+            Box::new(|issue| {
+                args.updated_after
+                    .is_none_or(|after| issue.updated_at.is_some_and(|updated| updated > after))
+            }),
+        ),
+        (
+            NEW_SINCE_LAST_RUN,
+            Box::new(move |issue| {
+                since.is_none_or(|since| issue.updated_at.is_some_and(|updated| updated >= since))
+            }),
+        ),
+        (
+            "by the author",
+            Box::new(|issue| {
+                args.issue_author
+                    .as_ref()
+                    .is_none_or(|author| issue.user.login.eq_ignore_ascii_case(author))
+            }),
+        ),
+        (
+            "in the milestone",
+            Box::new(|issue| match &args.milestone {
+                Some(name) => issue.milestone.as_ref().is_some_and(|m| m.matches(name)),
+                None => !args.no_milestone || issue.milestone.is_none(),
+            }),
+        ),
+        (
+            "with enough comments",
+            Box::new(|issue| args.min_comments.is_none_or(|min| issue.comments >= min)),
+        ),
+        (
+            "with few enough comments",
+            Box::new(|issue| args.max_comments.is_none_or(|max| issue.comments <= max)),
+        ),
+        (
+            "with enough reactions",
+            Box::new(|issue| {
+                args.min_reactions
+                    .is_none_or(|min| issue.reactions.total_count >= min)
+            }),
+        ),
+        (
+            match &args.assigned_to {
+                Some(_) => "assigned to the login",
+                None => "unassigned",
+            },
+            Box::new(|issue| match &args.assigned_to {
+                Some(login) => issue.is_assigned_to(login),
+                None => args.include_assigned || issue.assignees.is_empty(),
+            }),
+        ),
+    ]
+}
+
+/// Whether `issue` passes all of `filters`.
+fn is_candidate(filters: &[IssueFilter], issue: &Issue) -> bool {
+    filters.iter().all(|(_, keep)| keep(issue))
+}
+
+/// Formats `issue` like its `Display` impl, with the number in bold and the URL underlined.
+/// Styles are stripped again by `anstream` when color is disabled.
+fn decorate(issue: &Issue) -> String {
+    let number = Style::new().bold();
+    let url = Style::new()
+        .underline()
+        .fg_color(Some(AnsiColor::Cyan.into()));
+    format!(
+        "[{}{}{}] {} -> {}{}{}",
+        number.render(),
+        issue.number,
+        number.render_reset(),
+        sanitize(&issue.title),
+        url.render(),
+        issue.html_url,
+        url.render_reset()
+    )
+}
+
+/// `issue` as a markdown task list item linking to it, followed by its labels as inline code.
+fn markdown(repo: &Repo, issue: &Issue) -> String {
+    let title = sanitize(&issue.title)
+        .replace('\\', "\\\\")
+        .replace('[', "\\[")
+        .replace(']', "\\]");
+    let mut line = format!(
+        "- [ ] [{}#{} {}]({})",
+        repo, issue.number, title, issue.html_url
+    );
+    for label in &issue.labels {
+        line.push_str(&format!(" `{}`", sanitize(&label.name).replace('`', "'")));
+    }
+    line
+}
+
+/// The labels of `issue`, each on its own color with black or white text, whichever is easier
+/// to read. Labels without a valid color are rendered plainly.
+fn render_labels(issue: &Issue) -> String {
+    let mut out = String::new();
+    for label in &issue.labels {
+        let style = match label.rgb() {
+            Some((r, g, b)) => {
+                let luma = 0.299 * f64::from(r) + 0.587 * f64::from(g) + 0.114 * f64::from(b);
+                let text = if luma > 140.0 {
+                    AnsiColor::Black
+                } else {
+                    AnsiColor::BrightWhite
+                };
+                Style::new()
+                    .bg_color(Some(RgbColor(r, g, b).into()))
+                    .fg_color(Some(text.into()))
+            }
+            None => Style::new(),
+        };
+        out.push_str(&format!(
+            " {}{}{}",
+            style.render(),
+            sanitize(&label.name),
+            style.render_reset()
+        ));
+    }
+    out
+}
+
+/// Translates the filters into a search query, so GitHub does most of the filtering.
+async fn search_query(
+    args: &Args,
+    client: &GitHub,
+    authenticated: bool,
+    since: Option<Timestamp>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut terms = Vec::new();
+    match args.state {
+        IssueState::Open => terms.push("is:open".to_string()),
+        IssueState::Closed => terms.push("is:closed".to_string()),
+        IssueState::All => {}
+    }
+    if !args.include_prs {
+        terms.push("is:issue".to_string());
+    } else if args.exclude_drafts {
+        terms.push("-is:draft".to_string());
+    }
+    if !args.include_archived {
+        terms.push("archived:false".to_string());
+    }
+    if args.private_only {
+        terms.push("is:private".to_string());
+    } else if args.public_only {
+        terms.push("is:public".to_string());
+    }
+
+    terms.push(match (&args.repo, &args.org, &args.username) {
+        (Some(repo), _, _) => format!("repo:{}", repo),
+        (None, Some(org), _) => format!("org:{}", org),
+        (None, None, Some(username)) => format!("user:{}", username),
+        (None, None, None) if !authenticated => return Err(NOTHING_TO_ROLL_ON.into()),
+        (None, None, None) => format!("user:{}", get_authenticated_user(client).await?),
+    });
+
+    if let Some(language) = &args.language {
+        terms.push(format!("language:\"{}\"", language));
+    }
+    for topic in &args.topics {
+        terms.push(format!("topic:{}", topic));
+    }
+    for label in &args.labels {
+        terms.push(format!("label:\"{}\"", label));
+    }
+    for label in &args.exclude_labels {
+        terms.push(format!("-label:\"{}\"", label));
+    }
+    match &args.assigned_to {
+        Some(login) => terms.push(format!("assignee:{}", login)),
+        None if !args.include_assigned => terms.push("no:assignee".to_string()),
+        None => {}
+    }
+    if let Some(before) = args.updated_before {
+        terms.push(format!("updated:<{}", before));
+    }
+    if let Some(after) = args.updated_after {
+        terms.push(format!("updated:>{}", after));
+    }
+    if let Some(since) = since {
+        terms.push(format!("updated:>={}", since));
+    }
+    if let Some(author) = &args.issue_author {
+        terms.push(format!("author:{}", author));
+    }
+    if let Some(milestone) = &args.milestone {
+        terms.push(format!("milestone:\"{}\"", milestone));
+    } else if args.no_milestone {
+        terms.push("no:milestone".to_string());
+    }
+    if let Some(min) = args.min_comments {
+        terms.push(format!("comments:>={}", min));
+    }
+    if let Some(max) = args.max_comments {
+        terms.push(format!("comments:<={}", max));
+    }
+    if let Some(min) = args.min_reactions {
+        terms.push(format!("reactions:>={}", min));
+    }
+
+    Ok(terms.join(" "))
+}
+
+/// Identifies a repository listing, including everything that changes what it contains. Tokens
+/// are told apart by a digest, so another account's private repositories are never served.
+fn repo_list_key(args: &Args, token: Option<&HeaderValue>) -> String {
+    let affiliations = args
+        .affiliation
+        .iter()
+        .map(|affiliation| affiliation.as_str())
+        .collect::<Vec<_>>();
+    let auth = match args.installation_id {
+        Some(id) if args.app_id.is_some() => format!("installation:{}", id),
+        _ => match token {
+            Some(token) => {
+                let digest = ring::digest::digest(&ring::digest::SHA256, token.as_bytes());
+                let hex = digest.as_ref()[..8]
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect::<String>();
+                format!("token:{}", hex)
+            }
+            None => "anonymous".to_string(),
+        },
+    };
+    format!(
+        "{} {} {} {}",
+        args.base_url,
+        history_context(args),
+        auth,
+        affiliations.join(",")
+    )
+}
+
+/// Identifies what is rolled on, so the histories of different users and organizations stay apart.
+fn history_context(args: &Args) -> String {
+    if let Some(path) = &args.repos_file {
+        return format!("repos-file:{}", path.display());
+    }
+    match (&args.repo, &args.org, &args.username) {
+        (Some(repo), _, _) => format!("repo:{}", repo),
+        (None, Some(org), _) => format!("org:{}", org),
+        (None, None, Some(username)) if args.starred => format!("starred:{}", username),
+        (None, None, None) if args.starred => "starred".to_string(),
+        (None, None, Some(username)) => format!("user:{}", username),
+        (None, None, None) => "authenticated".to_string(),
+    }
+}
+
+/// Reads the owner/name entries of a --repos-file.
+fn read_repos_file(path: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read repos file {}: {}", path.display(), e))?;
+    let mut repos = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once('/') {
+            Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {
+                repos.push(line.to_string())
+            }
+            _ => {
+                return Err(format!(
+                    "{} line {}: expected owner/name, found '{}'",
+                    path.display(),
+                    index + 1,
+                    line
+                )
+                .into())
+            }
+        }
+    }
+    Ok(repos)
+}
+
+/// Opens `url` with the platform's default handler.
+fn open_in_browser(url: &str) -> std::io::Result<()> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+
+    let status = command
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!(
+            "opener exited with {}",
+            status
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A repository with one open issue, which every repository filter keeps by default.
+    fn repo(full_name: &str) -> Repo {
+        serde_json::from_value(fixtures::repo_json(full_name)).unwrap()
+    }
+
+    /// The names of the `repos` that pass the repository filters of `args`.
+    fn filtered(args: &Args, repos: &[Repo]) -> Vec<String> {
+        let repos = filter_repos(args, repos.to_vec());
+        repos.into_iter().map(|repo| repo.full_name).collect()
+    }
+
+    fn issue(repo: &str, number: u32, title: &str, labels: &[&str]) -> Issue {
+        serde_json::from_value(fixtures::issue_json(repo, number, title, labels)).unwrap()
+    }
+
+    fn pull_request(repo: &str, number: u32, draft: bool) -> Issue {
+        let mut pull = issue(repo, number, "Pull", &[]);
+        pull.pull_request = Some(serde_json::json!({}));
+        pull.draft = draft;
+        pull
+    }
+
+    /// The numbers of the `issues` that pass every issue filter of `args`.
+    fn kept(args: &Args, issues: &[Issue]) -> Vec<u32> {
+        let filters = issue_filters(args, None);
+        issues
+            .iter()
+            .filter(|issue| filters.iter().all(|(_, keep)| keep(issue)))
+            .map(|issue| issue.number)
+            .collect()
+    }
+
+    fn args(argv: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("issue-roulette").chain(argv.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn pull_requests_are_excluded_by_default() {
+        let issues = [
+            issue("octo/repo", 1, "Issue", &[]),
+            pull_request("octo/repo", 2, false),
+        ];
+        assert_eq!(kept(&args(&["-u", "octo"]), &issues), [1]);
+        assert_eq!(
+            kept(&args(&["-u", "octo", "--include-prs"]), &issues),
+            [1, 2]
+        );
+    }
+
+    #[test]
+    fn update_time_filters_skip_issues_without_timestamps() {
+        let updated = |number, updated_at: &str| Issue {
+            updated_at: Timestamp::parse_rfc3339(updated_at),
+            ..issue("octo/repo", number, "Issue", &[])
+        };
+        let issues = [
+            updated(1, "2024-01-01T00:00:00Z"),
+            updated(2, "2024-06-01T00:00:00Z"),
+            issue("octo/repo", 3, "Never updated", &[]),
+        ];
+        assert_eq!(kept(&args(&["-u", "octo"]), &issues), [1, 2, 3]);
+        let before = ["-u", "octo", "--updated-before", "2024-03-01T00:00:00Z"];
+        assert_eq!(kept(&args(&before), &issues), [1]);
+        let after = ["-u", "octo", "--updated-after", "2024-03-01T00:00:00Z"];
+        assert_eq!(kept(&args(&after), &issues), [2]);
+    }
+
+    #[test]
+    fn draft_pull_requests_can_be_excluded() {
+        let issues = [
+            issue("octo/repo", 1, "Issue", &[]),
+            pull_request("octo/repo", 2, true),
+            pull_request("octo/repo", 3, false),
+        ];
+        assert_eq!(
+            kept(&args(&["-u", "octo", "--include-prs"]), &issues),
+            [1, 2, 3]
+        );
+        assert_eq!(
+            kept(
+                &args(&["-u", "octo", "--include-prs", "--exclude-drafts"]),
+                &issues
+            ),
+            [1, 3]
+        );
+    }
+
+    #[test]
+    fn archived_repos_are_excluded_by_default() {
+        let repos = [
+            Repo {
+                archived: true,
+                ..repo("octo/archived")
+            },
+            repo("octo/active"),
+        ];
+        assert_eq!(filtered(&args(&["-u", "octo"]), &repos), ["octo/active"]);
+        assert_eq!(
+            filtered(&args(&["-u", "octo", "--include-archived"]), &repos),
+            ["octo/archived", "octo/active"]
+        );
+    }
+
+    #[test]
+    fn topic_filter_requires_every_topic() {
+        let with_topics = |name: &str, topics: &[&str]| Repo {
+            topics: topics.iter().map(|topic| topic.to_string()).collect(),
+            ..repo(name)
+        };
+        let repos = [
+            with_topics("octo/both", &["hacktoberfest", "Rust"]),
+            with_topics("octo/one", &["hacktoberfest"]),
+            with_topics("octo/none", &[]),
+        ];
+        assert_eq!(
+            filtered(&args(&["-u", "octo", "--topic", "hacktoberfest"]), &repos),
+            ["octo/both", "octo/one"]
+        );
+        assert_eq!(
+            filtered(
+                &args(&["-u", "octo", "--topic", "hacktoberfest", "--topic", "rust"]),
+                &repos
+            ),
+            ["octo/both"]
+        );
+    }
+
+    #[test]
+    fn fork_filter_states() {
+        let repos = [
+            Repo {
+                fork: true,
+                ..repo("octo/fork")
+            },
+            repo("octo/source"),
+        ];
+        let cases: [(&[&str], ForkFilter, &[&str]); 3] = [
+            (&[], ForkFilter::Exclude, &["octo/source"]),
+            (
+                &["--include-forked-repos"],
+                ForkFilter::Include,
+                &["octo/fork", "octo/source"],
+            ),
+            (&["--forks-only"], ForkFilter::Only, &["octo/fork"]),
+        ];
+        for (flags, state, kept) in cases {
+            let args = args(&[&["-u", "octo"], flags].concat());
+            assert_eq!(ForkFilter::from_args(&args), state);
+            assert_eq!(filtered(&args, &repos), kept, "with {:?}", flags);
+        }
+    }
+
+    /// `args` parsed from `argv` with `config` merged in, or the error merging gave.
+    fn configured(argv: &[&str], config: serde_json::Value) -> Result<Args, String> {
+        let argv = std::iter::once("issue-roulette").chain(argv.iter().copied());
+        let matches = Args::command().try_get_matches_from(argv).unwrap();
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        let config = serde_json::from_value(config).unwrap();
+        apply_config(&mut args, &matches, config).map_err(|e| e.to_string())?;
+        Ok(args)
+    }
+
+    #[test]
+    fn config_conflicts_are_rejected() {
+        use serde_json::json;
+        assert_eq!(
+            configured(&["--username", "octocat"], json!({ "org": "octo" })).unwrap_err(),
+            "Invalid config: 'org' can't be used with --username."
+        );
+        assert_eq!(
+            configured(&["--org", "octo"], json!({ "username": "octocat" })).unwrap_err(),
+            "Invalid config: 'username' can't be used with --org."
+        );
+        assert_eq!(
+            configured(
+                &["-u", "octo"],
+                json!({ "private_only": true, "public_only": true })
+            )
+            .unwrap_err(),
+            "Invalid config: 'private_only' and 'public_only' can't be used together."
+        );
+        assert_eq!(
+            configured(&["-u", "octo"], json!({ "verbose": true, "quiet": true })).unwrap_err(),
+            "Invalid config: 'verbose' and 'quiet' can't be used together."
+        );
+        assert_eq!(
+            configured(
+                &["-u", "octo", "--template", "{title}"],
+                json!({ "format": "json" })
+            )
+            .unwrap_err(),
+            "Invalid config: 'format' can't be used with --template."
+        );
+    }
+
+    #[test]
+    fn config_without_conflicts_is_merged() {
+        use serde_json::json;
+        let args = configured(&["-u", "octo"], json!({ "quiet": true })).unwrap();
+        assert!(args.quiet);
+        // Switched off, so not in the way of --public-only.
+        let config = json!({ "private_only": false, "verbose": false });
+        let args = configured(&["-u", "octo", "--public-only", "--quiet"], config).unwrap();
+        assert!(args.public_only && args.quiet && !args.private_only);
+        // The command line still wins over the config for the same setting.
+        let args = configured(&["--username", "octocat"], json!({ "username": "octo" })).unwrap();
+        assert_eq!(args.username.as_deref(), Some("octocat"));
+    }
+
+    #[test]
+    fn repo_list_key_tells_hosts_apart() {
+        let github = args(&["-u", "octocat"]);
+        let enterprise = args(&[
+            "-u",
+            "octocat",
+            "--base-url",
+            "https://ghe.example.com/api/v3",
+        ]);
+        let token = HeaderValue::from_static("Bearer one");
+        assert_ne!(
+            repo_list_key(&github, Some(&token)),
+            repo_list_key(&enterprise, Some(&token))
+        );
+        assert_eq!(
+            repo_list_key(&github, Some(&token)),
+            repo_list_key(&args(&["-u", "octocat"]), Some(&token))
+        );
+    }
+
+    #[test]
+    fn repo_list_key_tells_tokens_apart() {
+        let args = args(&["-u", "octocat"]);
+        let one = HeaderValue::from_static("Bearer one");
+        let other = HeaderValue::from_static("Bearer other");
+        let key = repo_list_key(&args, Some(&one));
+        assert_ne!(key, repo_list_key(&args, Some(&other)));
+        assert_ne!(key, repo_list_key(&args, None));
+        assert_eq!(key, repo_list_key(&args, Some(&one)));
+        assert!(!key.contains("one"), "the token itself must not be stored");
+    }
+
+    #[test]
+    fn markdown_sanitizes_title_and_labels() {
+        let issue = issue("octo/repo", 3, "[evil]\u{202e}txt", &["a\u{202e}`b"]);
+        assert_eq!(
+            markdown(&repo("octo/repo"), &issue),
+            "- [ ] [octo/repo#3 \\[evil\\]\\\\u{202e}txt](https://github.com/octo/repo/issues/3) `a\\u{202e}'b`"
+        );
+    }
+
+    /// The numbers of the issues `select_issues` draws from `pool` with the given seed.
+    fn drawn(pool: &[&(&Repo, Issue)], count: usize, argv: &[&str], seed: u64) -> Vec<u32> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let now = Timestamp::from_unix(0);
+        let drawn = select_issues(pool, count, &args(argv), now, &mut rng).unwrap();
+        drawn.iter().map(|(_, issue)| issue.number).collect()
+    }
+
+    #[test]
+    fn repo_selection_follows_the_seed() {
+        let repos = ["octo/a", "octo/b", "octo/c", "octo/d", "octo/e"].map(repo);
+        let repos = repos.iter().collect::<Vec<_>>();
+        let pick = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            select_repo(&repos, false, &mut rng)
+                .unwrap()
+                .full_name
+                .as_str()
+        };
+        assert_eq!(pick(1), "octo/e");
+        assert_eq!(pick(7), "octo/c");
+        assert_eq!(pick(42), "octo/b");
+    }
+
+    #[test]
+    fn weighted_repo_selection_favors_open_issues() {
+        let repos =
+            [("octo/a", 1), ("octo/b", 10), ("octo/c", 0)].map(|(name, open_issues)| Repo {
+                open_issues,
+                ..repo(name)
+            });
+        let repos = repos.iter().collect::<Vec<_>>();
+        for seed in [1, 7, 42] {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let picked = select_repo(&repos, true, &mut rng).unwrap();
+            assert_eq!(picked.full_name, "octo/b", "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn issue_selection_follows_the_seed() {
+        let owner = repo("octo/a");
+        let pool = [
+            (&owner, issue("octo/a", 1, "One", &[])),
+            (&owner, issue("octo/a", 2, "Two", &["bug"])),
+            (&owner, issue("octo/a", 3, "Three", &[])),
+            (&owner, issue("octo/a", 4, "Four", &["bug"])),
+        ];
+        let pool = pool.iter().collect::<Vec<_>>();
+        let uniform = ["-u", "octo"];
+        assert_eq!(drawn(&pool, 2, &uniform, 1), [3, 4]);
+        assert_eq!(drawn(&pool, 2, &uniform, 7), [2, 1]);
+        assert_eq!(drawn(&pool, 2, &uniform, 42), [1, 3]);
+        assert_eq!(drawn(&pool, 2, &uniform, 7), drawn(&pool, 2, &uniform, 7));
+
+        let preferred = [
+            "-u",
+            "octo",
+            "--prefer-label",
+            "bug",
+            "--prefer-weight",
+            "100",
+        ];
+        assert_eq!(drawn(&pool, 2, &preferred, 1), [2, 4]);
+        assert_eq!(drawn(&pool, 2, &preferred, 7), [4, 2]);
+        assert_eq!(drawn(&pool, 2, &preferred, 42), [2, 4]);
+    }
+
+    #[test]
+    fn count_beyond_the_pool_draws_all_of_it() {
+        let owner = repo("octo/a");
+        let pool = [1, 2, 3].map(|number| (&owner, issue("octo/a", number, "Issue", &[])));
+        let pool = pool.iter().collect::<Vec<_>>();
+        let mut numbers = drawn(&pool, 5, &["-u", "octo"], 1);
+        numbers.sort_unstable();
+        assert_eq!(numbers, [1, 2, 3]);
+    }
+
+    #[test]
+    fn empty_pools_yield_nothing() {
+        let mut rng = StdRng::seed_from_u64(1);
+        assert!(select_repo(&[], false, &mut rng).is_none());
+        assert!(select_repo(&[], true, &mut rng).is_none());
+        assert!(drawn(&[], 2, &["-u", "octo"], 1).is_empty());
     }
 }
@@ -0,0 +1,86 @@
+//! Where issue-roulette keeps its files, following the conventions of each platform.
+
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+const APP_DIR: &str = "issue-roulette";
+
+/// The directory for caches, e.g. `~/.cache/issue-roulette` honoring `XDG_CACHE_HOME`.
+/// Created if absent.
+pub fn cache_dir() -> PathBuf {
+    let base = if cfg!(windows) {
+        env_dir("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library").join("Caches"))
+    } else {
+        env_dir("XDG_CACHE_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".cache")))
+    };
+    app_dir(base)
+}
+
+/// The directory for state that outlives caches, such as the history, e.g.
+/// `~/.local/state/issue-roulette` honoring `XDG_STATE_HOME`. Created if absent.
+pub fn state_dir() -> PathBuf {
+    let base = if cfg!(windows) {
+        env_dir("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_dir("XDG_STATE_HOME")
+            .or_else(|| env_dir("HOME").map(|home| home.join(".local").join("state")))
+    };
+    app_dir(base)
+}
+
+/// The directory of the configuration file, e.g. `~/.config/issue-roulette` honoring
+/// `XDG_CONFIG_HOME`. Unlike the other directories there is no fallback, since a config in a
+/// shared temporary directory could have been planted by anyone.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env_dir("APPDATA")
+    } else {
+        env_dir("XDG_CONFIG_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".config")))
+    };
+    Some(base?.join(APP_DIR))
+}
+
+fn env_dir(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// `base/issue-roulette`, or a directory in the temporary directory if `base` is unknown.
+fn app_dir(base: Option<PathBuf>) -> PathBuf {
+    static WARN_FALLBACK: Once = Once::new();
+
+    let dir = match base {
+        Some(base) => base.join(APP_DIR),
+        None => {
+            let dir = std::env::temp_dir().join(APP_DIR);
+            WARN_FALLBACK.call_once(|| {
+                log::warn!(
+                    "Could not determine the home directory, keeping files in {}",
+                    dir.display()
+                )
+            });
+            dir
+        }
+    };
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        log::warn!("Failed to create {}: {}", dir.display(), e);
+    }
+    dir
+}
+
+/// Writes `value` as JSON to `path`, creating the directories leading to it.
+pub(crate) fn write_json(
+    path: &Path,
+    value: &impl serde::Serialize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string(value)?)?;
+    Ok(())
+}
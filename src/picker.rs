@@ -0,0 +1,71 @@
+//! Choosing one of many entries by hand on the terminal, narrowing them down by typing.
+
+use std::io::{BufRead, Write};
+
+/// What the user settled on in [`pick`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pick {
+    /// The entry at this index.
+    Entry(usize),
+    /// Leave it to chance among the entries at these indices, those left by the filter.
+    Random(Vec<usize>),
+    /// Nothing at all.
+    Quit,
+}
+
+/// Lists `entries`, which may be styled, numbered on stderr and reads the user's choice from stdin: a number picks
+/// that entry, other text narrows the list to entries matching it fuzzily, an empty line
+/// leaves it to chance among the listed entries and `q` or the end of input quits.
+pub fn pick(entries: &[String]) -> std::io::Result<Pick> {
+    let mut stderr = anstream::stderr().lock();
+    let mut lines = std::io::stdin().lock().lines();
+    let mut shown = (0..entries.len()).collect::<Vec<_>>();
+    loop {
+        for &index in &shown {
+            writeln!(stderr, "{:>4}) {}", index + 1, entries[index])?;
+        }
+        write!(
+            stderr,
+            "Pick a number, type to filter, Enter for a random one of these or q to quit: "
+        )?;
+        stderr.flush()?;
+
+        let Some(line) = lines.next().transpose()? else {
+            return Ok(Pick::Quit);
+        };
+        let input = line.trim();
+        match input.parse::<usize>() {
+            Ok(number) if (1..=entries.len()).contains(&number) => {
+                return Ok(Pick::Entry(number - 1))
+            }
+            Ok(_) => writeln!(stderr, "No entry {}.", input)?,
+            Err(_) if input.is_empty() => return Ok(Pick::Random(shown)),
+            Err(_) if input.eq_ignore_ascii_case("q") => return Ok(Pick::Quit),
+            Err(_) => {
+                let matching = (0..entries.len())
+                    .filter(|&index| {
+                        // Styling escapes would match digits and letters typed.
+                        let text = anstream::adapter::strip_str(&entries[index]).to_string();
+                        fuzzy_match(input, &text)
+                    })
+                    .collect::<Vec<_>>();
+                if matching.is_empty() {
+                    writeln!(stderr, "Nothing matches '{}'.", input)?;
+                } else {
+                    shown = matching;
+                }
+            }
+        }
+    }
+}
+
+/// Whether the characters of `pattern` occur in `text` in order, ignoring case and spaces,
+/// so `rmbug` matches `README bug`.
+fn fuzzy_match(pattern: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .all(|c| text.any(|t| t == c))
+}
@@ -0,0 +1,75 @@
+//! A spinner on stderr that shows what is being fetched while the user waits.
+
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const TICK: Duration = Duration::from_millis(100);
+
+/// Redraws `message` next to a spinning frame until finished or dropped. Hidden spinners,
+/// and spinners started while stderr or stdout isn't a terminal, draw nothing: piped output
+/// is read by a program, not someone waiting at the terminal.
+pub struct Spinner {
+    message: Arc<Mutex<String>>,
+    done: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    pub fn start(message: impl Into<String>) -> Self {
+        let mut spinner = Spinner::hidden();
+        *spinner.message.lock().unwrap() = message.into();
+        if !std::io::stderr().is_terminal() || !std::io::stdout().is_terminal() {
+            return spinner;
+        }
+
+        let message = Arc::clone(&spinner.message);
+        let done = Arc::clone(&spinner.done);
+        spinner.thread = Some(std::thread::spawn(move || {
+            for frame in FRAMES.iter().cycle() {
+                if done.load(Ordering::Relaxed) {
+                    break;
+                }
+                let message = message.lock().unwrap().clone();
+                eprint!("\r\x1b[2K{} {}", frame, message);
+                let _ = std::io::stderr().flush();
+                std::thread::sleep(TICK);
+            }
+            eprint!("\r\x1b[2K");
+        }));
+        spinner
+    }
+
+    pub fn hidden() -> Self {
+        Spinner {
+            message: Arc::default(),
+            done: Arc::default(),
+            thread: None,
+        }
+    }
+
+    pub fn set_message(&self, message: impl Into<String>) {
+        *self.message.lock().unwrap() = message.into();
+    }
+
+    /// Stops the spinner and clears its line, so regular output can follow.
+    pub fn finish(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
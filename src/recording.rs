@@ -0,0 +1,194 @@
+//! Recordings of the API responses of a run, which a later run can replay without a network.
+
+use reqwest::header::HeaderMap;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Bumped whenever the file layout changes.
+const RECORDING_VERSION: u32 = 1;
+
+/// Response headers worth keeping, those the client reads. Everything else is left out of
+/// recordings, which users may attach to bug reports.
+const KEPT_HEADERS: [&str; 7] = [
+    "content-type",
+    "link",
+    "retry-after",
+    "x-oauth-scopes",
+    "x-ratelimit-limit",
+    "x-ratelimit-remaining",
+    "x-ratelimit-reset",
+];
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+struct RecordedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl RecordedResponse {
+    fn new(status: reqwest::StatusCode, headers: &HeaderMap, body: &[u8]) -> Self {
+        let headers = KEPT_HEADERS
+            .iter()
+            .filter_map(|name| {
+                let value = headers.get(*name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect();
+        RecordedResponse {
+            status: status.as_u16(),
+            headers,
+            body: String::from_utf8_lossy(body).into_owned(),
+        }
+    }
+
+    fn to_response(&self) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.body(self.body.clone())?.into())
+    }
+}
+
+/// The responses to every request of a run, keyed by method and URL, and for requests with a
+/// body such as GraphQL queries by the body too.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct Recording {
+    version: u32,
+    responses: HashMap<String, RecordedResponse>,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Recording {
+            version: RECORDING_VERSION,
+            responses: HashMap::new(),
+        }
+    }
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read recording {}: {}", path.display(), e))?;
+        let recording = serde_json::from_str::<Recording>(&text)
+            .map_err(|e| format!("Invalid recording {}: {}", path.display(), e))?;
+        if recording.version != RECORDING_VERSION {
+            return Err(format!(
+                "Recording {} has version {}, expected {}",
+                path.display(),
+                recording.version,
+                RECORDING_VERSION
+            )
+            .into());
+        }
+        Ok(recording)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let text = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, text)
+            .map_err(|e| format!("Failed to write recording {}: {}", path.display(), e))?;
+        Ok(())
+    }
+
+    pub(crate) fn key(request: &reqwest::Request) -> String {
+        let body = request
+            .body()
+            .and_then(reqwest::Body::as_bytes)
+            .map(String::from_utf8_lossy);
+        match body {
+            Some(body) => format!("{} {} {}", request.method(), request.url(), body),
+            None => format!("{} {}", request.method(), request.url()),
+        }
+    }
+
+    /// Answers `request` with the response recorded for it.
+    pub(crate) fn replay(
+        &self,
+        request: &reqwest::Request,
+    ) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+        match self.responses.get(&Recording::key(request)) {
+            Some(response) => response.to_response(),
+            None => Err(format!(
+                "The recording holds no response to {} {}",
+                request.method(),
+                request.url()
+            )
+            .into()),
+        }
+    }
+
+    /// Keeps `res` as the response to the request with `key`, handing back an equivalent one
+    /// since reading the body consumes it.
+    pub(crate) async fn record(
+        recording: &std::sync::Mutex<Recording>,
+        key: String,
+        res: reqwest::Response,
+    ) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.bytes().await?;
+        let response = RecordedResponse::new(status, &headers, &body);
+        let copy = response.to_response()?;
+        recording.lock().unwrap().responses.insert(key, response);
+        Ok(copy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: reqwest::Method, url: &str, body: Option<&str>) -> reqwest::Request {
+        let builder = reqwest::Client::new().request(method, url);
+        match body {
+            Some(body) => builder.body(body.to_string()),
+            None => builder,
+        }
+        .build()
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn replays_what_was_recorded_without_private_headers() {
+        let response = http::Response::builder()
+            .status(200)
+            .header("Link", "<https://api.github.com/x?page=2>; rel=\"next\"")
+            .header("Set-Cookie", "session=secret")
+            .body("[1]")
+            .unwrap();
+        let get = request(reqwest::Method::GET, "https://api.github.com/x", None);
+        let recording = std::sync::Mutex::new(Recording::default());
+        let copy = Recording::record(&recording, Recording::key(&get), response.into())
+            .await
+            .unwrap();
+        assert_eq!(copy.text().await.unwrap(), "[1]");
+
+        let recording = recording.into_inner().unwrap();
+        let replayed = recording.replay(&get).unwrap();
+        assert_eq!(replayed.status(), 200);
+        assert!(replayed.headers().contains_key("link"));
+        assert!(!replayed.headers().contains_key("set-cookie"));
+        assert_eq!(replayed.text().await.unwrap(), "[1]");
+    }
+
+    #[test]
+    fn missing_responses_are_an_error() {
+        let recording = Recording::default();
+        let get = request(reqwest::Method::GET, "https://api.github.com/x", None);
+        assert_eq!(
+            recording.replay(&get).unwrap_err().to_string(),
+            "The recording holds no response to GET https://api.github.com/x"
+        );
+    }
+
+    #[test]
+    fn request_bodies_are_part_of_the_key() {
+        let url = "https://api.github.com/graphql";
+        let one = request(reqwest::Method::POST, url, Some("{\"query\":\"a\"}"));
+        let other = request(reqwest::Method::POST, url, Some("{\"query\":\"b\"}"));
+        assert_ne!(Recording::key(&one), Recording::key(&other));
+    }
+}
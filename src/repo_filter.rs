@@ -0,0 +1,449 @@
+//! Repository filter expressions such as `archived=false && stars>=10 && language=Rust`.
+
+use crate::{glob_match, Repo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    Fork,
+    Archived,
+    Private,
+    HasIssues,
+    OpenIssues,
+    Stars,
+    Language,
+    Topic,
+}
+
+impl Field {
+    const NAMES: [(&'static str, Field); 9] = [
+        ("name", Field::Name),
+        ("fork", Field::Fork),
+        ("archived", Field::Archived),
+        ("private", Field::Private),
+        ("has_issues", Field::HasIssues),
+        ("open_issues", Field::OpenIssues),
+        ("stars", Field::Stars),
+        ("language", Field::Language),
+        ("topic", Field::Topic),
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Bool(bool),
+    Number(u32),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Compare(Field, Op, Value),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn matches(&self, repo: &Repo) -> bool {
+        match self {
+            Expr::Not(expr) => !expr.matches(repo),
+            Expr::And(a, b) => a.matches(repo) && b.matches(repo),
+            Expr::Or(a, b) => a.matches(repo) || b.matches(repo),
+            Expr::Compare(field, op, value) => {
+                let actual = match field {
+                    Field::Fork => Value::Bool(repo.fork),
+                    Field::Archived => Value::Bool(repo.archived),
+                    Field::Private => Value::Bool(repo.private),
+                    Field::HasIssues => Value::Bool(repo.has_issues),
+                    Field::OpenIssues => Value::Number(repo.open_issues),
+                    Field::Stars => Value::Number(repo.stargazers_count),
+                    Field::Name => Value::Text(repo.full_name.clone()),
+                    Field::Language => Value::Text(repo.language.clone().unwrap_or_default()),
+                    Field::Topic => {
+                        let Value::Text(topic) = value else {
+                            return false;
+                        };
+                        return repo.has_topic(topic) == (*op == Op::Eq);
+                    }
+                };
+                let equal = match (&actual, value) {
+                    (Value::Text(name), Value::Text(pattern)) if *field == Field::Name => {
+                        glob_match(pattern, name)
+                    }
+                    (Value::Text(a), Value::Text(b)) => a.eq_ignore_ascii_case(b),
+                    (a, b) => a == b,
+                };
+                match (op, &actual, value) {
+                    (Op::Eq, _, _) => equal,
+                    (Op::Ne, _, _) => !equal,
+                    (op, Value::Number(a), Value::Number(b)) => match op {
+                        Op::Lt => a < b,
+                        Op::Le => a <= b,
+                        Op::Gt => a > b,
+                        _ => a >= b,
+                    },
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+/// A parsed `--repo-filter` expression: comparisons of repository fields joined with `&&`,
+/// `||` and `!`, grouped with parentheses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoFilterExpr(Expr);
+
+impl RepoFilterExpr {
+    pub fn matches(&self, repo: &Repo) -> bool {
+        self.0.matches(repo)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            Token::Word(word) => return write!(f, "'{}'", word),
+            Token::Op(Op::Eq) => "=",
+            Token::Op(Op::Ne) => "!=",
+            Token::Op(Op::Lt) => "<",
+            Token::Op(Op::Le) => "<=",
+            Token::Op(Op::Gt) => ">",
+            Token::Op(Op::Ge) => ">=",
+            Token::And => "&&",
+            Token::Or => "||",
+            Token::Not => "!",
+            Token::Open => "(",
+            Token::Close => ")",
+        };
+        write!(f, "'{}'", text)
+    }
+}
+
+/// Splits `text` into tokens, each with the character position it starts at.
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match (chars[i], next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('!', Some('=')) => (Token::Op(Op::Ne), 2),
+            ('<', Some('=')) => (Token::Op(Op::Le), 2),
+            ('>', Some('=')) => (Token::Op(Op::Ge), 2),
+            ('=', Some('=')) => (Token::Op(Op::Eq), 2),
+            ('=', _) => (Token::Op(Op::Eq), 1),
+            ('<', _) => (Token::Op(Op::Lt), 1),
+            ('>', _) => (Token::Op(Op::Gt), 1),
+            ('!', _) => (Token::Not, 1),
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            ('"', _) => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&c| c == '"')
+                    .ok_or_else(|| format!("unclosed '\"' at position {}", start + 1))?;
+                let word = chars[i + 1..i + 1 + end].iter().collect();
+                (Token::Word(word), end + 2)
+            }
+            (c, _) if c == '&' || c == '|' => {
+                return Err(format!(
+                    "unexpected '{}' at position {}, write '{}{}'",
+                    c,
+                    start + 1,
+                    c,
+                    c
+                ))
+            }
+            _ => {
+                let len = chars[i..]
+                    .iter()
+                    .position(|&c| c.is_whitespace() || "&|!<>=()\"".contains(c))
+                    .unwrap_or(chars.len() - i);
+                (Token::Word(chars[i..i + len].iter().collect()), len)
+            }
+        };
+        tokens.push((start + 1, token));
+        i += len;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn advance(&mut self) -> Result<(usize, Token), String> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or("unexpected end of the expression")?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn unexpected((position, token): (usize, Token), expected: &str) -> String {
+        format!(
+            "unexpected {} at position {}, expected {}",
+            token, position, expected
+        )
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.advance()? {
+            (_, Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            (_, Token::Open) => {
+                let expr = self.or()?;
+                match self.advance()? {
+                    (_, Token::Close) => Ok(expr),
+                    token => Err(Parser::unexpected(token, "')'")),
+                }
+            }
+            (position, Token::Word(name)) => self.compare(position, &name),
+            token => Err(Parser::unexpected(token, "a field name")),
+        }
+    }
+
+    fn compare(&mut self, position: usize, name: &str) -> Result<Expr, String> {
+        let field = Field::NAMES
+            .iter()
+            .find(|(field_name, _)| field_name.eq_ignore_ascii_case(name))
+            .map(|(_, field)| *field)
+            .ok_or_else(|| {
+                let names = Field::NAMES.map(|(name, _)| name);
+                format!(
+                    "unknown field '{}' at position {}, expected one of {}",
+                    name,
+                    position,
+                    names.join(", ")
+                )
+            })?;
+        let (op_position, op) = match self.advance()? {
+            (position, Token::Op(op)) => (position, op),
+            token => Err(Parser::unexpected(token, "a comparison such as '='"))?,
+        };
+        let (position, text) = match self.advance()? {
+            (position, Token::Word(text)) => (position, text),
+            token => Err(Parser::unexpected(token, "a value"))?,
+        };
+
+        let value = match field {
+            Field::Fork | Field::Archived | Field::Private | Field::HasIssues => {
+                match text.to_ascii_lowercase().as_str() {
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    _ => {
+                        return Err(format!(
+                            "invalid value '{}' at position {}, {} is true or false",
+                            text, position, name
+                        ))
+                    }
+                }
+            }
+            Field::OpenIssues | Field::Stars => Value::Number(text.parse().map_err(|_| {
+                format!(
+                    "invalid value '{}' at position {}, {} is a number",
+                    text, position, name
+                )
+            })?),
+            Field::Name | Field::Language | Field::Topic => Value::Text(text),
+        };
+        let ordered = matches!(value, Value::Number(_));
+        if !ordered && !matches!(op, Op::Eq | Op::Ne) {
+            return Err(format!(
+                "{} can only be compared with '=' or '!=', not {} at position {}",
+                name,
+                Token::Op(op),
+                op_position
+            ));
+        }
+        Ok(Expr::Compare(field, op, value))
+    }
+}
+
+impl std::str::FromStr for RepoFilterExpr {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            next: 0,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.tokens.get(parser.next).cloned() {
+            return Err(Parser::unexpected(token, "'&&' or '||'"));
+        }
+        Ok(RepoFilterExpr(expr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn matches(filter: &str, repo: &Repo) -> bool {
+        filter.parse::<RepoFilterExpr>().unwrap().matches(repo)
+    }
+
+    fn error(filter: &str) -> String {
+        filter.parse::<RepoFilterExpr>().unwrap_err()
+    }
+
+    /// A repository with 12 stars, 3 open issues, written in Rust and tagged `cli`.
+    fn rust_cli() -> Repo {
+        Repo {
+            stargazers_count: 12,
+            open_issues: 3,
+            language: Some("Rust".to_string()),
+            topics: vec!["cli".to_string()],
+            ..testing::repo("octo/roulette")
+        }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let repo = rust_cli();
+        // Read as `fork=false || (archived=false && stars>100)`.
+        assert!(matches("fork=false || archived=false && stars>100", &repo));
+        assert!(!matches(
+            "(fork=false || archived=false) && stars>100",
+            &repo
+        ));
+        assert_eq!(
+            "fork=false || archived=false && stars>100".parse::<RepoFilterExpr>(),
+            "fork=false || (archived=false && stars>100)".parse::<RepoFilterExpr>()
+        );
+    }
+
+    #[test]
+    fn not_applies_to_the_next_term() {
+        let repo = rust_cli();
+        assert!(matches("!fork=true && !archived=true", &repo));
+        assert!(!matches("!(fork=false || stars>100)", &repo));
+        assert!(matches("!!fork=false", &repo));
+    }
+
+    #[test]
+    fn names_are_globbed_and_languages_ignore_case() {
+        let repo = rust_cli();
+        assert!(matches("name=octo/*", &repo));
+        assert!(matches("name=\"*/roul*\"", &repo));
+        assert!(!matches("name=other/*", &repo));
+        assert!(matches("name!=other/*", &repo));
+        assert!(matches("language=rust", &repo));
+        assert!(matches("LANGUAGE=RUST", &repo));
+        assert!(!matches("language=go", &repo));
+        let unknown = testing::repo("octo/empty");
+        assert!(matches("language!=rust", &unknown));
+    }
+
+    #[test]
+    fn topics_are_matched_as_a_set() {
+        let repo = rust_cli();
+        assert!(matches("topic=cli", &repo));
+        assert!(matches("topic=CLI", &repo));
+        assert!(!matches("topic!=cli", &repo));
+        assert!(matches("topic!=web", &repo));
+    }
+
+    #[test]
+    fn numbers_are_ordered() {
+        let repo = rust_cli();
+        assert!(matches("stars>=12 && stars<=12 && stars=12", &repo));
+        assert!(matches("stars>11 && stars<13 && stars!=11", &repo));
+        assert!(!matches("stars>12", &repo));
+        assert!(matches("open_issues>=1 && open_issues<4", &repo));
+        assert!(!matches("open_issues<3", &repo));
+    }
+
+    #[test]
+    fn errors_name_the_problem_and_its_position() {
+        assert_eq!(
+            error("color=red"),
+            "unknown field 'color' at position 1, expected one of name, fork, archived, private, \
+             has_issues, open_issues, stars, language, topic"
+        );
+        assert_eq!(
+            error("fork=maybe"),
+            "invalid value 'maybe' at position 6, fork is true or false"
+        );
+        assert_eq!(
+            error("stars>=lots"),
+            "invalid value 'lots' at position 8, stars is a number"
+        );
+        assert_eq!(
+            error("fork=true & stars>1"),
+            "unexpected '&' at position 11, write '&&'"
+        );
+        assert_eq!(
+            error("fork=true | stars>1"),
+            "unexpected '|' at position 11, write '||'"
+        );
+        assert_eq!(error("name=\"octo/a"), "unclosed '\"' at position 6");
+        assert_eq!(
+            error("language<Rust"),
+            "language can only be compared with '=' or '!=', not '<' at position 9"
+        );
+        assert_eq!(
+            error("fork=true stars>1"),
+            "unexpected 'stars' at position 11, expected '&&' or '||'"
+        );
+        assert_eq!(error("fork="), "unexpected end of the expression");
+        assert_eq!(error("(fork=true"), "unexpected end of the expression");
+    }
+}
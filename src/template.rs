@@ -0,0 +1,137 @@
+//! User supplied output templates such as `{repo}#{number}: {title}`.
+
+use crate::{sanitize, Issue, Repo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Number,
+    Title,
+    Url,
+    Repo,
+    Labels,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Field(Field),
+}
+
+/// A parsed output template. `{{` and `}}` stand for literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template(Vec<Segment>);
+
+impl Template {
+    /// Placeholders a template may use.
+    pub const PLACEHOLDERS: [&'static str; 5] = ["number", "title", "url", "repo", "labels"];
+
+    pub fn render(&self, repo: &Repo, issue: &Issue) -> String {
+        let mut out = String::new();
+        for segment in &self.0 {
+            match segment {
+                Segment::Text(text) => out.push_str(text),
+                Segment::Field(Field::Number) => out.push_str(&issue.number.to_string()),
+                Segment::Field(Field::Title) => out.push_str(&sanitize(&issue.title)),
+                Segment::Field(Field::Url) => out.push_str(&issue.html_url),
+                Segment::Field(Field::Repo) => out.push_str(&repo.full_name),
+                Segment::Field(Field::Labels) => {
+                    let names = issue.labels.iter().map(|label| sanitize(&label.name));
+                    out.push_str(&names.collect::<Vec<_>>().join(", "));
+                }
+            }
+        }
+        out
+    }
+}
+
+impl std::str::FromStr for Template {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed {
+                        return Err("unclosed '{', write '{{' for a literal brace".to_string());
+                    }
+                    let field = match name.as_str() {
+                        "number" => Field::Number,
+                        "title" => Field::Title,
+                        "url" => Field::Url,
+                        "repo" => Field::Repo,
+                        "labels" => Field::Labels,
+                        _ => {
+                            return Err(format!(
+                                "unknown placeholder {{{}}}, expected one of {}",
+                                name,
+                                Template::PLACEHOLDERS
+                                    .map(|p| format!("{{{}}}", p))
+                                    .join(", ")
+                            ))
+                        }
+                    };
+                    if !literal.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field));
+                }
+                '}' => return Err("unmatched '}', write '}}' for a literal brace".to_string()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Text(literal));
+        }
+        Ok(Template(segments))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn renders_fields() {
+        let template = "{repo}#{number} {{{title}}} {labels}"
+            .parse::<Template>()
+            .unwrap();
+        let issue = testing::labeled_issue("octo/repo", 7, &["bug", "help wanted"]);
+        assert_eq!(
+            template.render(&testing::repo("octo/repo"), &issue),
+            "octo/repo#7 {Labeled} bug, help wanted"
+        );
+    }
+
+    #[test]
+    fn sanitizes_title_and_labels() {
+        let template = "{title} {labels}".parse::<Template>().unwrap();
+        let mut issue = testing::labeled_issue("octo/repo", 7, &["a\u{202e}b"]);
+        issue.title = "evil\u{202e}txt.exe\nnext".to_string();
+        assert_eq!(
+            template.render(&testing::repo("octo/repo"), &issue),
+            "evil\\u{202e}txt.exe next a\\u{202e}b"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! A minimal HTTP server standing in for the GitHub API in tests. It answers every request
+//! from a handler and remembers what was asked for. Shared with the integration tests, so it
+//! uses nothing of the crate itself.
+#![allow(dead_code)] // Not every test uses every part of it.
+
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A request as the handler sees it.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// The path with the query, e.g. `/users/octocat/repos?per_page=100`.
+    pub path: String,
+    pub body: String,
+}
+
+/// What the handler answers with.
+#[derive(Debug, Clone)]
+pub struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Reply {
+    /// A `200 OK` with `body` as JSON.
+    pub fn json(body: impl ToString) -> Self {
+        Reply::status(200, body)
+    }
+
+    /// A JSON response with the given status.
+    pub fn status(status: u16, body: impl ToString) -> Self {
+        Reply {
+            status,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: body.to_string(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: impl ToString) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+type Handler = dyn Fn(&Request) -> Reply + Send + Sync;
+
+pub struct TestServer {
+    /// The root to point the client at, e.g. `http://127.0.0.1:41234`.
+    pub url: String,
+    listener: Mutex<Option<TcpListener>>,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+    /// Listens on a free local port. Nothing is answered before [`TestServer::serve`].
+    pub async fn bind() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        TestServer {
+            url,
+            listener: Mutex::new(Some(listener)),
+            requests: Arc::default(),
+        }
+    }
+
+    /// Answers requests with `handler` from now on, until the runtime shuts down.
+    pub fn serve(&self, handler: impl Fn(&Request) -> Reply + Send + Sync + 'static) {
+        let listener = self
+            .listener
+            .lock()
+            .unwrap()
+            .take()
+            .expect("already serving");
+        let handler: Arc<Handler> = Arc::new(handler);
+        let requests = self.requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let requests = requests.clone();
+                tokio::spawn(async move { answer(stream, handler.as_ref(), &requests).await });
+            }
+        });
+    }
+
+    /// The paths of the requests received so far, in order.
+    pub fn paths(&self) -> Vec<String> {
+        let requests = self.requests.lock().unwrap();
+        requests
+            .iter()
+            .map(|request| request.path.clone())
+            .collect()
+    }
+}
+
+/// Reads one request from `stream`, adds it to `requests`, answers it and closes the
+/// connection.
+async fn answer(
+    mut stream: TcpStream,
+    handler: &Handler,
+    requests: &Mutex<Vec<Request>>,
+) -> Option<()> {
+    let mut data = Vec::new();
+    let mut buf = [0; 4096];
+    let head_end = loop {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buf[..n]);
+        if let Some(end) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&data[..head_end]).into_owned();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    while data.len() < head_end + length {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..n]);
+    }
+    let body = String::from_utf8_lossy(&data[head_end..]).into_owned();
+
+    let request = Request { method, path, body };
+    let reply = handler(&request);
+    requests.lock().unwrap().push(request);
+    let mut response = format!("HTTP/1.1 {} Test\r\n", reply.status);
+    for (name, value) in &reply.headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        reply.body.len(),
+        reply.body
+    ));
+    stream.write_all(response.as_bytes()).await.ok()?;
+    stream.shutdown().await.ok()
+}
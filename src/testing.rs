@@ -0,0 +1,18 @@
+//! Typed fixtures for the unit tests, built from the shared [`crate::fixtures`].
+
+use crate::{Issue, Repo};
+
+pub(crate) use crate::fixtures::{issue_json, repo_json};
+
+pub(crate) fn issue(repo: &str, number: u32, title: &str) -> Issue {
+    serde_json::from_value(issue_json(repo, number, title, &[])).unwrap()
+}
+
+/// `issue` carrying labels with these names.
+pub(crate) fn labeled_issue(repo: &str, number: u32, labels: &[&str]) -> Issue {
+    serde_json::from_value(issue_json(repo, number, "Labeled", labels)).unwrap()
+}
+
+pub(crate) fn repo(full_name: &str) -> Repo {
+    serde_json::from_value(repo_json(full_name)).unwrap()
+}
@@ -0,0 +1,227 @@
+//! How many requests may be in flight at once, narrowed as the rate limit budget runs low.
+
+use crate::{RateLimitedError, Timestamp, MAX_RATE_LIMIT_WAIT};
+use reqwest::header::HeaderMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Below this many remaining requests, fewer are sent at once, down to one at a time shortly
+/// before the budget is used up.
+const LOW_BUDGET: u64 = 100;
+
+struct State {
+    in_flight: usize,
+    max: usize,
+    /// The budget as of the latest response, unknown until one arrived.
+    remaining: Option<u64>,
+    reset: Option<Timestamp>,
+    pausing: bool,
+}
+
+impl State {
+    fn limit(&self) -> usize {
+        match self.remaining {
+            Some(remaining) if remaining < LOW_BUDGET => {
+                ((self.max as u64 * remaining / LOW_BUDGET) as usize).max(1)
+            }
+            _ => self.max,
+        }
+    }
+
+    /// How long until the budget resets, if it is used up.
+    fn exhausted_for(&self, now: Timestamp) -> Option<Duration> {
+        let reset = self.reset.filter(|_| self.remaining == Some(0))?;
+        // A second more, as GitHub's clock may be a little behind.
+        (reset > now).then(|| Duration::from_secs((reset.unix() - now.unix()) as u64 + 1))
+    }
+}
+
+pub(crate) struct Throttle {
+    state: Mutex<State>,
+    released: Notify,
+}
+
+/// A slot for one request, given back when dropped.
+pub(crate) struct Permit<'a>(&'a Throttle);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().in_flight -= 1;
+        self.0.released.notify_waiters();
+    }
+}
+
+impl Throttle {
+    pub(crate) fn new(max: usize) -> Self {
+        Throttle {
+            state: Mutex::new(State {
+                in_flight: 0,
+                max: max.max(1),
+                remaining: None,
+                reset: None,
+                pausing: false,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Allows up to `max` requests at once while the budget lasts, returning the previous
+    /// maximum.
+    pub(crate) fn set_max(&self, max: usize) -> usize {
+        let previous = std::mem::replace(&mut self.state.lock().unwrap().max, max.max(1));
+        self.released.notify_waiters();
+        previous
+    }
+
+    /// Waits for a slot, and while the budget is used up until it resets. Fails right away if
+    /// that takes longer than we are willing to wait.
+    pub(crate) async fn acquire(&self) -> Result<Permit<'_>, Box<dyn std::error::Error>> {
+        loop {
+            // Created before checking, so a release in between isn't missed.
+            let released = self.released.notified();
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                match state.exhausted_for(Timestamp::now()) {
+                    Some(wait) => {
+                        if !std::mem::replace(&mut state.pausing, true) {
+                            log::warn!(
+                                "Rate limit used up, pausing {}s until it resets",
+                                wait.as_secs()
+                            );
+                        }
+                        Some(wait)
+                    }
+                    None if state.in_flight < state.limit() => {
+                        state.in_flight += 1;
+                        return Ok(Permit(self));
+                    }
+                    None => None,
+                }
+            };
+            match wait {
+                Some(wait) if wait > MAX_RATE_LIMIT_WAIT => {
+                    return Err(Box::new(RateLimitedError(wait)))
+                }
+                Some(wait) => {
+                    tokio::time::sleep(wait).await;
+                    let mut state = self.state.lock().unwrap();
+                    state.remaining = None;
+                    state.pausing = false;
+                }
+                None => released.await,
+            }
+        }
+    }
+
+    /// Takes note of the budget a response reports in its `X-RateLimit-*` headers.
+    pub(crate) fn observe(&self, headers: &HeaderMap) {
+        let header = |name| headers.get(name)?.to_str().ok()?.parse::<i64>().ok();
+        let Some(remaining) = header("X-RateLimit-Remaining") else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        let before = state.limit();
+        state.remaining = Some(remaining.max(0) as u64);
+        state.reset = header("X-RateLimit-Reset").map(Timestamp::from_unix);
+        let after = state.limit();
+        if after < before {
+            log::info!(
+                "{} requests left in the rate limit, sending at most {} at once",
+                remaining,
+                after
+            );
+        } else if after > before {
+            self.released.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Reply, TestServer};
+    use crate::{
+        build_http_client, get_issues_for_repos, testing, GitHub, HttpOptions, IssueQuery,
+    };
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    fn remaining(throttle: &Throttle, remaining: u64) -> usize {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-RateLimit-Remaining", remaining.into());
+        throttle.observe(&headers);
+        throttle.state.lock().unwrap().limit()
+    }
+
+    #[test]
+    fn limit_narrows_as_budget_drops() {
+        let throttle = Throttle::new(8);
+        assert_eq!(throttle.state.lock().unwrap().limit(), 8);
+        assert_eq!(remaining(&throttle, 4000), 8);
+        assert_eq!(remaining(&throttle, 50), 4);
+        assert_eq!(remaining(&throttle, 10), 1);
+        assert_eq!(remaining(&throttle, 1), 1);
+        // And widens again once the budget is replenished.
+        assert_eq!(remaining(&throttle, 5000), 8);
+    }
+
+    #[tokio::test]
+    async fn low_budget_allows_one_request_at_a_time() {
+        let throttle = Throttle::new(8);
+        remaining(&throttle, 5);
+        let permit = throttle.acquire().await.unwrap();
+        let second = tokio::time::timeout(Duration::from_millis(50), throttle.acquire()).await;
+        assert!(second.is_err(), "second request was let through");
+        drop(permit);
+        assert!(throttle.acquire().await.is_ok());
+    }
+
+    /// How long the server takes to answer each request in `requests_slow_down_as_the_budget_drops`.
+    const DELAY: Duration = Duration::from_millis(200);
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn requests_slow_down_as_the_budget_drops() {
+        let server = TestServer::bind().await;
+        let arrivals = Arc::new(Mutex::new(Vec::new()));
+        let seen = arrivals.clone();
+        server.serve(move |_| {
+            let remaining = {
+                let mut arrivals = seen.lock().unwrap();
+                arrivals.push(Instant::now());
+                40 - arrivals.len()
+            };
+            std::thread::sleep(DELAY);
+            Reply::json("[]").header("X-RateLimit-Remaining", remaining)
+        });
+        let http = build_http_client(&None, &HttpOptions::default()).unwrap();
+        let client = GitHub::new(http)
+            .with_base_url(server.url.clone())
+            .with_concurrency(4);
+        let fetch = |names: [&str; 4]| {
+            let repos = names.map(testing::repo);
+            let client = &client;
+            async move {
+                let query = IssueQuery::default();
+                get_issues_for_repos(client, &repos, &query, 4, Duration::from_secs(10), |_| {})
+                    .await
+                    .unwrap();
+            }
+        };
+
+        // Nothing is known of the budget yet, so all four go out at once.
+        fetch(["octo/a", "octo/b", "octo/c", "octo/d"]).await;
+        // Around 36 requests left narrow it down to one at a time.
+        fetch(["octo/e", "octo/f", "octo/g", "octo/h"]).await;
+
+        let arrivals = arrivals.lock().unwrap();
+        assert_eq!(arrivals.len(), 8);
+        let (first, second) = arrivals.split_at(4);
+        let spread = *first.iter().max().unwrap() - *first.iter().min().unwrap();
+        assert!(spread < DELAY, "first requests were {:?} apart", spread);
+        for pair in second.windows(2) {
+            let gap = pair[1] - pair[0];
+            assert!(gap >= DELAY, "later requests were only {:?} apart", gap);
+        }
+    }
+}
@@ -0,0 +1,338 @@
+//! Minimal UTC timestamp handling for the RFC 3339 dates GitHub returns.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A point in time, stored as whole seconds since the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub fn now() -> Self {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Timestamp(since_epoch.as_secs() as i64)
+    }
+
+    pub fn from_unix(seconds: i64) -> Self {
+        Timestamp(seconds)
+    }
+
+    pub fn unix(self) -> i64 {
+        self.0
+    }
+
+    /// Parses timestamps such as `2024-01-31T12:00:00Z` or `2024-01-31T13:00:00.5+01:00`.
+    pub fn parse_rfc3339(text: &str) -> Option<Self> {
+        let (date, time) = text.split_once(['T', 't', ' '])?;
+
+        let mut date_parts = date.splitn(3, '-');
+        let year = date_parts.next()?.parse::<i64>().ok()?;
+        let month = date_parts.next()?.parse::<u32>().ok()?;
+        let day = date_parts.next()?.parse::<u32>().ok()?;
+        if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+            return None;
+        }
+
+        let (clock, offset) = match time.find(['Z', 'z', '+', '-']) {
+            Some(index) => time.split_at(index),
+            None => return None,
+        };
+        let offset = match offset {
+            "Z" | "z" => 0,
+            _ => {
+                let sign = if offset.starts_with('-') { -1 } else { 1 };
+                let (hours, minutes) = offset[1..].split_once(':')?;
+                sign * (hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60)
+            }
+        };
+
+        let clock = clock.split('.').next()?;
+        let mut clock_parts = clock.splitn(3, ':');
+        let hour = clock_parts.next()?.parse::<i64>().ok()?;
+        let minute = clock_parts.next()?.parse::<i64>().ok()?;
+        let second = clock_parts.next()?.parse::<i64>().ok()?;
+        if hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+
+        let days = days_from_civil(year, month, day);
+        Some(Timestamp(
+            days * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second - offset,
+        ))
+    }
+
+    /// Formats in the local time zone with its offset, e.g. `2024-01-31 13:00:00 +01:00`.
+    /// Falls back to UTC where the time zone can't be determined.
+    pub fn format_local(self) -> String {
+        let offset = local_offset(self.0);
+        let local = Timestamp(self.0 + offset).to_string();
+        let sign = if offset < 0 { '-' } else { '+' };
+        format!(
+            "{} {} {}{:02}:{:02}",
+            &local[..10],
+            &local[11..19],
+            sign,
+            offset.abs() / 3600,
+            offset.abs() % 3600 / 60
+        )
+    }
+
+    /// The time elapsed between this timestamp and `now`, zero if it lies in the future.
+    pub fn age(self, now: Timestamp) -> Duration {
+        Duration::from_secs(now.0.saturating_sub(self.0).max(0) as u64)
+    }
+}
+
+impl std::ops::Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    /// Saturates at the earliest representable time.
+    fn sub(self, duration: Duration) -> Timestamp {
+        match i64::try_from(duration.as_secs()) {
+            Ok(seconds) => Timestamp(self.0.saturating_sub(seconds)),
+            Err(_) => Timestamp(i64::MIN),
+        }
+    }
+}
+
+impl std::fmt::Display for Timestamp {
+    /// Formats as RFC 3339 in UTC, e.g. `2024-01-31T12:00:00Z`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let days = self.0.div_euclid(SECONDS_PER_DAY);
+        let seconds = self.0.rem_euclid(SECONDS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year,
+            month,
+            day,
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        )
+    }
+}
+
+impl std::str::FromStr for Timestamp {
+    type Err = String;
+
+    /// Accepts an RFC 3339 timestamp or a duration like `30d`, meaning that long ago.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Timestamp::parse_rfc3339(text)
+            .or_else(|| parse_duration(text).map(|ago| Timestamp::now() - ago))
+            .ok_or_else(|| {
+                format!(
+                    "'{}' is neither an RFC 3339 timestamp nor a duration like 30d",
+                    text
+                )
+            })
+    }
+}
+
+impl serde::Serialize for Timestamp {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Timestamp {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Timestamp::parse_rfc3339(&text)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp '{}'", text)))
+    }
+}
+
+/// Seconds the local time zone is ahead of UTC at `unix`, as configured for the C library.
+#[cfg(unix)]
+fn local_offset(unix: i64) -> i64 {
+    let time = unix as libc::time_t;
+    // SAFETY: `localtime_r` only reads `time` and writes the zeroed `tm`, which outlives it.
+    unsafe {
+        let mut tm = std::mem::zeroed::<libc::tm>();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return 0;
+        }
+        tm.tm_gmtoff as i64
+    }
+}
+
+#[cfg(not(unix))]
+fn local_offset(_unix: i64) -> i64 {
+    0
+}
+
+/// Parses durations such as `90s`, `15m`, `12h`, `30d` or `2w`.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let unit_index = text.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = text.split_at(unit_index);
+    let amount = amount.parse::<u64>().ok()?;
+    let unit_seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(amount.checked_mul(unit_seconds)?))
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of the given proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> i64 {
+        Timestamp::parse_rfc3339(text)
+            .unwrap_or_else(|| panic!("{} didn't parse", text))
+            .unix()
+    }
+
+    #[test]
+    fn parses_utc_and_offsets() {
+        assert_eq!(parse("1970-01-01T00:00:00Z"), 0);
+        assert_eq!(parse("2024-01-31T12:00:00Z"), 1_706_702_400);
+        assert_eq!(parse("2024-01-31t12:00:00z"), 1_706_702_400);
+        assert_eq!(parse("2024-01-31T13:30:00+01:30"), 1_706_702_400);
+        assert_eq!(parse("2024-01-31T07:00:00-05:00"), 1_706_702_400);
+        // An offset can move the date across midnight.
+        assert_eq!(parse("2024-02-01T00:00:00+12:00"), 1_706_702_400);
+    }
+
+    #[test]
+    fn ignores_fractional_seconds() {
+        assert_eq!(parse("2024-01-31T12:00:00.5Z"), 1_706_702_400);
+        assert_eq!(parse("2024-01-31T13:00:00.999999+01:00"), 1_706_702_400);
+    }
+
+    #[test]
+    fn rejects_invalid_timestamps() {
+        for text in [
+            "",
+            "2024-01-31",
+            "2024-01-31T12:00:00",
+            "2024-01-31T12:00Z",
+            "2024-13-01T00:00:00Z",
+            "2024-00-10T00:00:00Z",
+            "2024-01-32T00:00:00Z",
+            "2024-04-31T00:00:00Z",
+            "2023-02-29T00:00:00Z",
+            "2024-01-31T24:00:00Z",
+            "2024-01-31T12:60:00Z",
+            "2024-01-31T12:00:00+0100",
+            "yesterday",
+        ] {
+            assert_eq!(Timestamp::parse_rfc3339(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn handles_leap_years_and_month_ends() {
+        let day = SECONDS_PER_DAY;
+        assert_eq!(
+            parse("2024-03-01T00:00:00Z") - parse("2024-02-28T00:00:00Z"),
+            2 * day
+        );
+        assert_eq!(
+            parse("2023-03-01T00:00:00Z") - parse("2023-02-28T00:00:00Z"),
+            day
+        );
+        // Centuries are leap years only if divisible by 400.
+        assert_eq!(parse("2000-02-29T00:00:00Z"), 951_782_400);
+        assert_eq!(Timestamp::parse_rfc3339("1900-02-29T00:00:00Z"), None);
+        assert_eq!(
+            parse("2025-01-01T00:00:00Z") - parse("2024-12-31T00:00:00Z"),
+            day
+        );
+        assert_eq!(
+            Timestamp::from_unix(parse("2024-02-29T23:59:59Z") + 1).to_string(),
+            "2024-03-01T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn formatting_round_trips() {
+        for text in [
+            "1970-01-01T00:00:00Z",
+            "1969-12-31T23:59:59Z",
+            "2000-02-29T12:34:56Z",
+            "2024-12-31T23:59:59Z",
+            "2100-03-01T00:00:00Z",
+        ] {
+            let timestamp = Timestamp::parse_rfc3339(text).unwrap();
+            assert_eq!(timestamp.to_string(), text);
+            assert_eq!(
+                Timestamp::parse_rfc3339(&timestamp.to_string()),
+                Some(timestamp)
+            );
+        }
+    }
+
+    #[test]
+    fn subtraction_saturates() {
+        let now = Timestamp::from_unix(1_706_702_400);
+        assert_eq!((now - Duration::from_secs(400)).unix(), 1_706_702_000);
+        assert_eq!(now - Duration::from_secs(u64::MAX), Timestamp(i64::MIN));
+        assert_eq!(
+            Timestamp(-10) - Duration::from_secs(i64::MAX as u64),
+            Timestamp(i64::MIN)
+        );
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(
+            parse_duration("30d"),
+            Some(Duration::from_secs(30 * 86_400))
+        );
+        assert_eq!(parse_duration("2w"), Some(Duration::from_secs(14 * 86_400)));
+        assert_eq!(parse_duration("30"), None);
+        assert_eq!(parse_duration("d"), None);
+        assert_eq!(parse_duration("3y"), None);
+        assert_eq!(parse_duration("99999999999999999999w"), None);
+    }
+}
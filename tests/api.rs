@@ -0,0 +1,247 @@
+//! End-to-end tests against a fake GitHub API: the library's fetching and the binary's
+//! filtering, pointed at a local server with --base-url.
+
+#[path = "../src/fixtures.rs"]
+mod fixtures;
+#[path = "../src/test_server.rs"]
+mod test_server;
+
+use fixtures::{issue_json, repo_json};
+use issue_roulette::{
+    build_http_client, get_issues, get_public_repos, GitHub, HttpOptions, IssueQuery, Repo,
+};
+use serde_json::{json, Value};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use test_server::{Reply, TestServer};
+
+fn client(server: &TestServer) -> GitHub {
+    let http = build_http_client(&None, &HttpOptions::default()).unwrap();
+    GitHub::new(http).with_base_url(server.url.clone())
+}
+
+/// Runs the binary against the API at `url` with `args`, isolated from the user's config,
+/// caches and tokens.
+async fn run(url: &str, args: &[&str]) -> Output {
+    let home = std::env::temp_dir().join(format!(
+        "issue-roulette-api-{}-{}",
+        std::process::id(),
+        url.rsplit(':').next().unwrap()
+    ));
+    let mut command = Command::new(env!("CARGO_BIN_EXE_issue-roulette"));
+    command
+        .args(["--base-url", url, "--no-history", "--no-cache"])
+        .args(["--format", "json"])
+        .args(args)
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_CACHE_HOME", home.join("cache"))
+        .env("XDG_STATE_HOME", home.join("state"));
+    for name in issue_roulette::TOKEN_ENV_VARS {
+        command.env_remove(name);
+    }
+    let output = tokio::task::spawn_blocking(move || command.output())
+        .await
+        .unwrap()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&home);
+    output
+}
+
+/// Like [`run`], expecting success, and returns the numbers of the issues it printed.
+async fn run_binary(url: &str, args: &[&str]) -> Vec<u64> {
+    let output = run(url, args).await;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            serde_json::from_str::<Value>(line).unwrap()["number"]
+                .as_u64()
+                .unwrap()
+        })
+        .collect()
+}
+
+/// Answers for `octo/repo` holding the issues numbered 1 to 3.
+fn serve_repo(server: &TestServer) {
+    server.serve(|request| {
+        if request.path.starts_with("/repos/octo/repo/issues") {
+            let issues = (1..=3)
+                .map(|number| issue_json("octo/repo", number, "Issue", &[]))
+                .collect::<Vec<_>>();
+            Reply::json(json!(issues))
+        } else {
+            Reply::json(repo_json("octo/repo"))
+        }
+    });
+}
+
+#[tokio::test]
+async fn pagination_is_followed() {
+    let server = TestServer::bind().await;
+    let next = format!(
+        "<{}/users/octocat/repos?per_page=100&page=2>; rel=\"next\"",
+        server.url
+    );
+    server.serve(move |request| {
+        if request.path.ends_with("&page=2") {
+            Reply::json(json!([repo_json("octocat/c")]))
+        } else {
+            let page = json!([repo_json("octocat/a"), repo_json("octocat/b")]);
+            Reply::json(page).header("Link", &next)
+        }
+    });
+
+    let repos = get_public_repos(&client(&server), "octocat").await.unwrap();
+    let names = repos
+        .iter()
+        .map(|repo| repo.full_name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["octocat/a", "octocat/b", "octocat/c"]);
+    assert_eq!(server.paths().len(), 2);
+}
+
+#[tokio::test]
+async fn pull_requests_are_excluded() {
+    let server = TestServer::bind().await;
+    server.serve(|request| {
+        if request.path.starts_with("/repos/octo/repo/issues") {
+            let mut pull = issue_json("octo/repo", 2, "Issue", &[]);
+            pull["pull_request"] =
+                json!({ "url": "https://api.github.com/repos/octo/repo/pulls/2" });
+            Reply::json(json!([issue_json("octo/repo", 1, "Issue", &[]), pull]))
+        } else {
+            Reply::json(repo_json("octo/repo"))
+        }
+    });
+
+    let numbers = run_binary(&server.url, &["--repo", "octo/repo", "--list"]).await;
+    assert_eq!(numbers, [1]);
+    let numbers = run_binary(
+        &server.url,
+        &["--repo", "octo/repo", "--list", "--include-prs"],
+    )
+    .await;
+    assert_eq!(numbers, [1, 2]);
+}
+
+#[tokio::test]
+async fn label_filters_apply() {
+    let server = TestServer::bind().await;
+    server.serve(|request| {
+        if request.path.starts_with("/repos/octo/repo/issues") {
+            Reply::json(json!([
+                issue_json("octo/repo", 1, "Issue", &["bug"]),
+                issue_json("octo/repo", 2, "Issue", &["bug", "wontfix"]),
+                issue_json("octo/repo", 3, "Issue", &["docs"]),
+            ]))
+        } else {
+            Reply::json(repo_json("octo/repo"))
+        }
+    });
+
+    let numbers = run_binary(
+        &server.url,
+        &["--repo", "octo/repo", "--list", "--label", "bug"],
+    )
+    .await;
+    assert_eq!(numbers, [1, 2]);
+    let args = [
+        "--repo",
+        "octo/repo",
+        "--list",
+        "--label",
+        "bug",
+        "--exclude-label",
+        "wontfix",
+    ];
+    assert_eq!(run_binary(&server.url, &args).await, [1]);
+}
+
+#[tokio::test]
+async fn rate_limit_is_backed_off() {
+    let server = TestServer::bind().await;
+    let attempts = AtomicUsize::new(0);
+    server.serve(move |_| {
+        if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+            let body = json!({ "message": "API rate limit exceeded" });
+            Reply::status(403, body).header("Retry-After", 1)
+        } else {
+            Reply::json(json!([issue_json("octo/repo", 1, "Issue", &[])]))
+        }
+    });
+
+    let started = Instant::now();
+    let repo = Repo::named("octo/repo".to_string());
+    let issues = get_issues(&client(&server), &repo, &IssueQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(server.paths().len(), 2);
+    assert!(
+        started.elapsed() >= Duration::from_secs(1),
+        "retried without waiting"
+    );
+}
+
+#[test]
+fn replay_answers_without_the_server() {
+    let recording = std::env::temp_dir().join(format!(
+        "issue-roulette-recording-{}.json",
+        std::process::id()
+    ));
+    let recording = recording.to_str().unwrap();
+    let runtime = || {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    };
+
+    // The server goes away with the runtime it was started on.
+    let (url, recorded) = runtime().block_on(async {
+        let server = TestServer::bind().await;
+        serve_repo(&server);
+        let args = ["--repo", "octo/repo", "--list", "--record", recording];
+        (server.url.clone(), run_binary(&server.url, &args).await)
+    });
+    assert!(std::net::TcpStream::connect(url.trim_start_matches("http://")).is_err());
+    assert_eq!(recorded, [1, 2, 3]);
+
+    let args = ["--repo", "octo/repo", "--list", "--replay", recording];
+    let replayed = runtime().block_on(run_binary(&url, &args));
+    assert_eq!(replayed, recorded);
+
+    let args = ["--repo", "octo/other", "--list", "--replay", recording];
+    let output = runtime().block_on(run(&url, &args));
+    std::fs::remove_file(recording).unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let missing = format!(
+        "The recording holds no response to GET {}/repos/octo/other",
+        url
+    );
+    assert!(stderr.contains(&missing), "{}", stderr);
+}
+
+#[tokio::test]
+async fn search_without_a_source_or_token_asks_for_one() {
+    let server = TestServer::bind().await;
+    server.serve(|_| Reply::json(json!({ "login": "octocat" })));
+
+    let output = run(&server.url, &["--search"]).await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Nothing to roll on: pass --username"),
+        "{}",
+        stderr
+    );
+    assert!(server.paths().is_empty());
+}